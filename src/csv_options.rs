@@ -0,0 +1,189 @@
+use anyhow::Error;
+
+use crate::sql::string_literal;
+
+/// Options for reading a csv file, set in the csv import options dialog.
+///
+/// Empty strings and `None` are left to DuckDB's sniffer, so the default options
+/// read the file exactly like a plain `read_csv_auto` call.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CsvOptions {
+    pub delimiter: String,
+    pub quote: String,
+    pub escape: String,
+    pub encoding: String,
+    pub header: Option<bool>,
+    pub skip_rows: u32,
+    pub date_format: String,
+    pub timestamp_format: String,
+    pub null_string: String,
+    /// Column names and the DuckDB type to read them as, e.g. `("zip", "VARCHAR")`
+    pub column_types: Vec<(String, String)>,
+}
+
+impl CsvOptions {
+    /// The named arguments for `read_csv_auto`, each starting with `, `.
+    /// Returns an empty string for the default options.
+    pub fn scan_arguments(&self) -> String {
+        let mut arguments = String::new();
+        let text_options = [
+            ("delim", unescape_delimiter(&self.delimiter)),
+            ("quote", self.quote.clone()),
+            ("escape", self.escape.clone()),
+            ("encoding", self.encoding.clone()),
+            ("dateformat", self.date_format.clone()),
+            ("timestampformat", self.timestamp_format.clone()),
+            ("nullstr", self.null_string.clone()),
+        ];
+        for (name, value) in text_options.iter() {
+            if !value.is_empty() {
+                arguments.push_str(&format!(", {} = {}", name, string_literal(value)));
+            }
+        }
+
+        if let Some(header) = self.header {
+            arguments.push_str(&format!(", header = {}", header));
+        }
+        if self.skip_rows > 0 {
+            arguments.push_str(&format!(", skip = {}", self.skip_rows));
+        }
+        if !self.column_types.is_empty() {
+            let types: Vec<String> = self
+                .column_types
+                .iter()
+                .map(|(column, column_type)| {
+                    format!(
+                        "{}: {}",
+                        string_literal(column),
+                        string_literal(column_type)
+                    )
+                })
+                .collect();
+            arguments.push_str(&format!(", types = {{{}}}", types.join(", ")));
+        }
+
+        arguments
+    }
+}
+
+/// Parses the column type overrides typed in the options dialog, written as
+/// `column:TYPE` pairs separated by commas, e.g. `zip:VARCHAR, amount:DECIMAL(10,2)`.
+///
+/// Commas inside parentheses belong to the type.
+pub fn parse_column_types(text: &str) -> Result<Vec<(String, String)>, Error> {
+    let mut column_types = Vec::new();
+    for entry in split_top_level(text) {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+        let (column, column_type) = entry
+            .split_once(':')
+            .ok_or_else(|| Error::msg(format!("Expected 'column:TYPE' but got '{}'", entry)))?;
+        let (column, column_type) = (column.trim(), column_type.trim());
+        if column.is_empty() || column_type.is_empty() {
+            return Err(Error::msg(format!(
+                "Expected 'column:TYPE' but got '{}'",
+                entry
+            )));
+        }
+        column_types.push((column.to_string(), column_type.to_string()));
+    }
+    Ok(column_types)
+}
+
+/// Writes column type overrides back in the format read by `parse_column_types`.
+pub fn format_column_types(column_types: &[(String, String)]) -> String {
+    column_types
+        .iter()
+        .map(|(column, column_type)| format!("{}:{}", column, column_type))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+// Splits on commas that are not inside parentheses.
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+// A tab can't be typed into a line edit, so `\t` is accepted for it.
+fn unescape_delimiter(delimiter: &str) -> String {
+    if delimiter == "\\t" {
+        "\t".to_string()
+    } else {
+        delimiter.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_options_have_no_arguments() {
+        assert_eq!(CsvOptions::default().scan_arguments(), "");
+    }
+
+    #[test]
+    fn test_scan_arguments() {
+        let options = CsvOptions {
+            delimiter: ";".to_string(),
+            quote: "'".to_string(),
+            encoding: "latin-1".to_string(),
+            header: Some(false),
+            skip_rows: 3,
+            date_format: "%d.%m.%Y".to_string(),
+            null_string: "n/a".to_string(),
+            column_types: vec![("zip".to_string(), "VARCHAR".to_string())],
+            ..CsvOptions::default()
+        };
+        assert_eq!(
+            options.scan_arguments(),
+            ", delim = ';', quote = '''', encoding = 'latin-1', dateformat = '%d.%m.%Y', nullstr = 'n/a', header = false, skip = 3, types = {'zip': 'VARCHAR'}"
+        );
+    }
+
+    #[test]
+    fn test_tab_delimiter() {
+        let options = CsvOptions {
+            delimiter: "\\t".to_string(),
+            ..CsvOptions::default()
+        };
+        assert_eq!(options.scan_arguments(), ", delim = '\t'");
+    }
+
+    #[test]
+    fn test_parse_column_types() {
+        let column_types = parse_column_types("zip:VARCHAR, amount : DECIMAL(10,2),").unwrap();
+        assert_eq!(
+            column_types,
+            vec![
+                ("zip".to_string(), "VARCHAR".to_string()),
+                ("amount".to_string(), "DECIMAL(10,2)".to_string()),
+            ]
+        );
+        assert_eq!(
+            format_column_types(&column_types),
+            "zip:VARCHAR, amount:DECIMAL(10,2)"
+        );
+
+        assert!(parse_column_types("").unwrap().is_empty());
+        assert!(parse_column_types("zip").is_err());
+        assert!(parse_column_types("zip:").is_err());
+    }
+}
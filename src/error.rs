@@ -0,0 +1,416 @@
+use anyhow::Error;
+use std::fmt;
+use std::io;
+
+/// Why the loader could not open a file or load a page, shown to the user with a
+/// title and message per kind of failure.
+///
+/// Attached to the error as its outermost context by `LoaderError::attach`, so the
+/// DuckDB message and the rest of the chain are kept underneath it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoaderError {
+    UnsupportedFormat {
+        filename: String,
+    },
+    FileNotFound {
+        filename: String,
+    },
+    PermissionDenied {
+        filename: String,
+    },
+    /// A row of a csv or json file or the footer of a parquet file could not be read
+    Parse {
+        filename: String,
+        /// The 1-based line of the file, when DuckDB reports it
+        line: Option<u64>,
+        /// The column whose value could not be converted, when DuckDB reports it
+        column: Option<String>,
+        message: String,
+    },
+    /// DuckDB hit its memory limit and could not spill to the temp directory
+    OutOfMemory {
+        message: String,
+    },
+    /// The query was interrupted by the cancel button. Attached by the loader, which
+    /// knows which request was cancelled, and never taken from the DuckDB message
+    Cancelled,
+    /// DuckDB rejected a query, e.g. a typo in the sql editor or a filter value that
+    /// doesn't fit its column
+    Sql {
+        message: String,
+    },
+    /// Any other failure. `filename` is empty when no file is involved
+    Read {
+        filename: String,
+        message: String,
+    },
+    /// The loader thread is gone, no file can be read until the viewer is restarted
+    Stopped,
+}
+
+impl LoaderError {
+    /// Classifies `error`, which occurred while reading `filename`. A `LoaderError`
+    /// already in the chain is returned as it is, otherwise the kind is taken from the
+    /// io errors in the chain and the message of DuckDB.
+    pub fn classify(error: &Error, filename: &str) -> LoaderError {
+        // finds the kind attached as the context of any layer of the error
+        if let Some(loader_error) = error.downcast_ref::<LoaderError>() {
+            return loader_error.clone();
+        }
+        let filename = filename.to_string();
+        let io_error = error
+            .downcast_ref::<io::Error>()
+            .or_else(|| error.chain().find_map(|e| e.downcast_ref::<io::Error>()));
+        if let Some(io_error) = io_error {
+            match io_error.kind() {
+                io::ErrorKind::NotFound => return LoaderError::FileNotFound { filename },
+                io::ErrorKind::PermissionDenied => {
+                    return LoaderError::PermissionDenied { filename }
+                }
+                _ => {}
+            }
+        }
+
+        let message = error.root_cause().to_string();
+        let lower = message.to_lowercase();
+        if lower.contains("out of memory") {
+            LoaderError::OutOfMemory { message }
+        } else if lower.contains("permission denied") {
+            LoaderError::PermissionDenied { filename }
+        } else if ["no such file", "no files found", "file not found"]
+            .iter()
+            .any(|pattern| lower.contains(pattern))
+        {
+            LoaderError::FileNotFound { filename }
+        } else if [
+            "csv error",
+            "when sniffing",
+            "malformed json",
+            "magic bytes",
+            "corrupt",
+        ]
+        .iter()
+        .any(|pattern| lower.contains(pattern))
+        {
+            LoaderError::Parse {
+                filename,
+                line: error_line(&lower),
+                column: error_column(&message),
+                message,
+            }
+        } else if [
+            "parser error",
+            "binder error",
+            "catalog error",
+            "conversion error",
+            "syntax error",
+        ]
+        .iter()
+        .any(|pattern| lower.contains(pattern))
+        {
+            LoaderError::Sql { message }
+        } else {
+            LoaderError::Read { filename, message }
+        }
+    }
+
+    /// Classifies `error` and attaches the result as its outermost context, so the
+    /// ui can show it with `LoaderError::of`.
+    pub fn attach(error: Error, filename: &str) -> Error {
+        if error.downcast_ref::<LoaderError>().is_some() {
+            return error;
+        }
+        let loader_error = LoaderError::classify(&error, filename);
+        error.context(loader_error)
+    }
+
+    /// The kind of an error returned by the loader. Errors that were never attached
+    /// a kind are shown as `Read` errors.
+    pub fn of(error: &Error) -> LoaderError {
+        LoaderError::classify(error, "")
+    }
+
+    /// The title of the error dialog.
+    pub fn title(&self) -> &'static str {
+        match self {
+            LoaderError::UnsupportedFormat { .. } => "Unsupported File Type",
+            LoaderError::FileNotFound { .. } => "File Not Found",
+            LoaderError::PermissionDenied { .. } => "Permission Denied",
+            LoaderError::Parse { .. } => "Error Parsing File",
+            LoaderError::OutOfMemory { .. } => "Out of Memory",
+            LoaderError::Cancelled => "Query Cancelled",
+            LoaderError::Sql { .. } => "Query Error",
+            LoaderError::Read { .. } => "Error Reading File",
+            LoaderError::Stopped => "Loader Stopped",
+        }
+    }
+
+    /// The file the error occurred in, if the kind names one.
+    pub fn filename(&self) -> Option<&str> {
+        match self {
+            LoaderError::UnsupportedFormat { filename }
+            | LoaderError::FileNotFound { filename }
+            | LoaderError::PermissionDenied { filename }
+            | LoaderError::Parse { filename, .. }
+            | LoaderError::Read { filename, .. } => {
+                Some(filename.as_str()).filter(|filename| !filename.is_empty())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoaderError::UnsupportedFormat { filename } => write!(
+                f,
+                "'{}' is not a file type the viewer can open. Open a csv, json, parquet, \
+                 arrow, database or xlsx file",
+                filename
+            ),
+            LoaderError::FileNotFound { filename } => {
+                write!(f, "'{}' does not exist or was moved", filename)
+            }
+            LoaderError::PermissionDenied { filename } => {
+                write!(f, "Permission to read '{}' was denied", filename)
+            }
+            LoaderError::Parse {
+                filename,
+                line,
+                column,
+                message,
+            } => {
+                write!(f, "'{}' could not be parsed", filename)?;
+                if let Some(line) = line {
+                    write!(f, " at line {}", line)?;
+                }
+                if let Some(column) = column {
+                    write!(f, " in column '{}'", column)?;
+                }
+                write!(f, "\n\n{}", message)
+            }
+            LoaderError::OutOfMemory { message } => write!(
+                f,
+                "DuckDB ran out of memory. Raise the memory limit or set a temp directory \
+                 on the Settings page\n\n{}",
+                message
+            ),
+            LoaderError::Cancelled => write!(f, "The query was cancelled"),
+            LoaderError::Sql { message } => write!(f, "{}", message),
+            LoaderError::Read { filename, message } if filename.is_empty() => {
+                write!(f, "{}", message)
+            }
+            LoaderError::Read { filename, message } => {
+                write!(f, "Error reading file '{}': {}", filename, message)
+            }
+            LoaderError::Stopped => write!(
+                f,
+                "The loader thread stopped, restart the viewer to open files again"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LoaderError {}
+
+/// The context of an error of a generated query, a message along with the sql so the
+/// error details can show what was run.
+#[derive(Debug)]
+pub struct FailedQuery {
+    message: String,
+    sql: String,
+}
+
+impl FailedQuery {
+    pub fn new(message: impl Into<String>, sql: &str) -> FailedQuery {
+        FailedQuery {
+            message: message.into(),
+            sql: sql.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for FailedQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// The text of the error details panel, which can be copied into a bug report: the
+/// kind of `error`, the file it occurred in, the sql that failed and every message
+/// of the chain down to the one from DuckDB.
+///
+/// `filename` is the opened file, used when the kind of the error doesn't name one.
+pub fn error_details(error: &Error, filename: &str) -> String {
+    let loader_error = LoaderError::of(error);
+    let message = loader_error.to_string();
+    let filename = loader_error.filename().unwrap_or(filename);
+
+    let mut details = format!("{}\n{}\n", loader_error.title(), message);
+    if !filename.is_empty() {
+        details.push_str(&format!("\nFile: {}", filename));
+    }
+    if let Some(query) = error.downcast_ref::<FailedQuery>() {
+        details.push_str(&format!("\nSQL: {}", query.sql));
+    }
+    // the kind is the first message of the chain and already shown above
+    let causes: Vec<String> = error
+        .chain()
+        .map(|cause| cause.to_string())
+        .filter(|cause| *cause != message)
+        .collect();
+    if !causes.is_empty() {
+        details.push_str("\n\nCaused by:");
+        for (index, cause) in causes.iter().enumerate() {
+            details.push_str(&format!("\n  {}: {}", index, cause));
+        }
+    }
+    details
+}
+
+// The line number DuckDB reports in a lower-case message, e.g. `CSV Error on Line: 3`
+// or `at byte 12 in line 4`. Only these prefixes are read, other mentions of a line
+// such as `Original Line: 42,abc` quote the contents of the file
+fn error_line(lower: &str) -> Option<u64> {
+    ["error on line: ", " in line "].iter().find_map(|prefix| {
+        let start = lower.find(prefix)? + prefix.len();
+        let digits: String = lower[start..]
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        digits.parse().ok()
+    })
+}
+
+// The quoted name after `column` in a DuckDB message, e.g.
+// `Error when converting column "price".`
+fn error_column(message: &str) -> Option<String> {
+    let start = message.find("column \"")? + "column \"".len();
+    let end = message[start..].find('"')?;
+    Some(message[start..start + end].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+    use duckdb::Connection;
+
+    #[test]
+    fn test_classify_csv_parse_error() {
+        let error = Error::msg(
+            "Conversion Error: CSV Error on Line: 3\n\
+             Original Line: widget,abc\n\
+             Error when converting column \"price\". Could not convert string \"abc\" to 'DOUBLE'",
+        )
+        .context("Failed to read the columns");
+        match LoaderError::classify(&error, "prices.csv") {
+            LoaderError::Parse {
+                filename,
+                line,
+                column,
+                ..
+            } => {
+                assert_eq!(filename, "prices.csv");
+                assert_eq!(line, Some(3));
+                assert_eq!(column.as_deref(), Some("price"));
+            }
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_classify_line_prefixes() {
+        // the quoted contents of the line are not its number
+        let error = Error::msg(
+            "Invalid Input Error: CSV Error\n\
+             Original Line: 42,abc\n\
+             Error when converting column \"price\".",
+        );
+        match LoaderError::classify(&error, "prices.csv") {
+            LoaderError::Parse { line, .. } => assert_eq!(line, None),
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+
+        let error = Error::msg("Invalid Input Error: Malformed JSON at byte 12 in line 4");
+        match LoaderError::classify(&error, "products.json") {
+            LoaderError::Parse { line, .. } => assert_eq!(line, Some(4)),
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_classify_interrupt_is_not_cancelled() {
+        // only the loader knows whether the user cancelled the query
+        let error = Error::msg("INTERRUPT Error: Interrupted!");
+        assert!(matches!(
+            LoaderError::classify(&error, "data.csv"),
+            LoaderError::Read { .. }
+        ));
+        let cancelled = Error::msg("INTERRUPT Error: Interrupted!").context(LoaderError::Cancelled);
+        assert_eq!(LoaderError::of(&cancelled), LoaderError::Cancelled);
+    }
+
+    #[test]
+    fn test_classify_duckdb_errors() -> Result<(), Error> {
+        let conn = Connection::open_in_memory()?;
+        let missing = conn
+            .execute_batch("SELECT * FROM read_parquet('test_data/missing.parquet')")
+            .context("Failed to read the columns")
+            .unwrap_err();
+        assert_eq!(
+            LoaderError::classify(&missing, "test_data/missing.parquet"),
+            LoaderError::FileNotFound {
+                filename: "test_data/missing.parquet".to_string()
+            }
+        );
+
+        let typo: Error = conn.execute_batch("SELEC 1").unwrap_err().into();
+        assert!(matches!(
+            LoaderError::classify(&typo, "data.csv"),
+            LoaderError::Sql { .. }
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_attach_keeps_the_first_kind() {
+        let denied = io::Error::new(io::ErrorKind::PermissionDenied, "denied");
+        let error = LoaderError::attach(Error::new(denied), "secret.csv");
+        let error = LoaderError::attach(error.context("Error opening the file"), "other.csv");
+        assert_eq!(
+            LoaderError::of(&error),
+            LoaderError::PermissionDenied {
+                filename: "secret.csv".to_string()
+            }
+        );
+        assert_eq!(LoaderError::of(&error).title(), "Permission Denied");
+
+        // errors that were never attached a kind
+        let unknown = Error::msg("No file is open");
+        assert_eq!(LoaderError::of(&unknown).to_string(), "No file is open");
+    }
+
+    #[test]
+    fn test_error_details() {
+        let error = Error::msg("Conversion Error: CSV Error on Line: 3")
+            .context(FailedQuery::new(
+                "Failed to get row",
+                "SELECT * FROM read_csv('prices.csv') LIMIT 20 OFFSET 0",
+            ))
+            .context("Error applying the filters");
+        let error = LoaderError::attach(error, "prices.csv");
+
+        let details = error_details(&error, "");
+        assert!(details.starts_with("Error Parsing File\n'prices.csv' could not be parsed"));
+        assert!(details.contains("\nFile: prices.csv\n"));
+        assert!(details.contains("\nSQL: SELECT * FROM read_csv('prices.csv') LIMIT 20 OFFSET 0"));
+        assert!(details.contains("\n  0: Error applying the filters\n  1: Failed to get row"));
+        assert!(details.ends_with("  2: Conversion Error: CSV Error on Line: 3"));
+
+        // the opened file is shown for kinds that don't name one
+        let stopped = Error::new(LoaderError::Stopped);
+        assert!(error_details(&stopped, "data.parquet").contains("File: data.parquet"));
+    }
+}
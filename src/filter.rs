@@ -0,0 +1,305 @@
+use anyhow::Error;
+
+use crate::model::{ColumnKind, ColumnSchema};
+use crate::sql::quote_identifier;
+
+/// A comparison offered in the filter bar. Which ones are offered for a column
+/// depends on its `ColumnKind`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterOperator {
+    Equals,
+    /// Case-insensitive substring match on the text of the value
+    Contains,
+    /// Regular expression match on the text of the value
+    Matches,
+    /// An inclusive range, either bound may be left empty
+    Between,
+    Before,
+    After,
+    IsNull,
+    IsNotNull,
+}
+
+impl FilterOperator {
+    /// Parses the operator label shown in the filter bar, e.g. `is not null`.
+    pub fn from_label(label: &str) -> Option<FilterOperator> {
+        match label {
+            "equals" => Some(FilterOperator::Equals),
+            "contains" => Some(FilterOperator::Contains),
+            "matches" => Some(FilterOperator::Matches),
+            "between" => Some(FilterOperator::Between),
+            "before" => Some(FilterOperator::Before),
+            "after" => Some(FilterOperator::After),
+            "is null" => Some(FilterOperator::IsNull),
+            "is not null" => Some(FilterOperator::IsNotNull),
+            _ => None,
+        }
+    }
+}
+
+/// A predicate on one column of the table, set in the filter bar.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColumnFilter {
+    /// The 0-indexed column the predicate applies to
+    pub column: usize,
+    pub operator: FilterOperator,
+    pub value: String,
+    /// The upper bound of `Between`, unused by the other operators
+    pub value2: String,
+}
+
+/// Builds the WHERE clause for `filters` and the search box text `search` over the
+/// columns in `schema`, combined with AND.
+///
+/// Values are never written into the sql. They are returned as parameters in the
+/// order of their `?` placeholders and cast to the type of their column.
+/// Returns an empty clause and no parameters when there are no filters and no search.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// * A filter refers to a column that is not in `schema`
+/// * A filter other than `is null` and `is not null` has no value
+pub fn where_clause(
+    schema: &[ColumnSchema],
+    filters: &[ColumnFilter],
+    search: &str,
+) -> Result<(String, Vec<String>), Error> {
+    let mut predicates = Vec::new();
+    let mut params = Vec::new();
+
+    for filter in filters {
+        let column = schema
+            .get(filter.column)
+            .ok_or_else(|| Error::msg(format!("No column at index {}", filter.column)))?;
+        let name = quote_identifier(&column.name);
+        let placeholder = typed_placeholder(column);
+
+        let predicate = match filter.operator {
+            FilterOperator::IsNull => format!("{} IS NULL", name),
+            FilterOperator::IsNotNull => format!("{} IS NOT NULL", name),
+            FilterOperator::Between => {
+                let mut bounds = Vec::new();
+                if !filter.value.is_empty() {
+                    bounds.push(format!("{} >= {}", name, placeholder));
+                    params.push(filter.value.clone());
+                }
+                if !filter.value2.is_empty() {
+                    bounds.push(format!("{} <= {}", name, placeholder));
+                    params.push(filter.value2.clone());
+                }
+                if bounds.is_empty() {
+                    return Err(Error::msg(format!(
+                        "The range filter on '{}' needs a lower or upper bound",
+                        column.name
+                    )));
+                }
+                bounds.join(" AND ")
+            }
+            operator => {
+                if filter.value.is_empty() {
+                    return Err(Error::msg(format!(
+                        "The filter on '{}' needs a value",
+                        column.name
+                    )));
+                }
+                params.push(filter.value.clone());
+                match operator {
+                    FilterOperator::Contains => {
+                        format!("contains(lower(CAST({} AS VARCHAR)), lower(?))", name)
+                    }
+                    FilterOperator::Matches => {
+                        format!("regexp_matches(CAST({} AS VARCHAR), ?)", name)
+                    }
+                    FilterOperator::Before => format!("{} < {}", name, placeholder),
+                    FilterOperator::After => format!("{} > {}", name, placeholder),
+                    _ => format!("{} = {}", name, placeholder),
+                }
+            }
+        };
+        predicates.push(format!("({})", predicate));
+    }
+
+    if !search.is_empty() && !schema.is_empty() {
+        predicates.push(format!("({})", search_predicate(schema)));
+        params.extend(schema.iter().map(|_| search.to_string()));
+    }
+
+    if predicates.is_empty() {
+        return Ok((String::new(), params));
+    }
+    Ok((format!(" WHERE {}", predicates.join(" AND ")), params))
+}
+
+// Matches rows with the search text in any column, compared as lower-case text in
+// DuckDB's text form of each value. Numbers and dates read the same as in the table,
+// timestamps are `2024-01-02 09:30:00` where the table shows `2024-01-02T09:30:00`.
+// Takes one parameter per column
+fn search_predicate(schema: &[ColumnSchema]) -> String {
+    schema
+        .iter()
+        .map(|column| {
+            format!(
+                "contains(lower(CAST({} AS VARCHAR)), lower(?))",
+                quote_identifier(&column.name)
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(" OR ")
+}
+
+/// Marks each case-insensitive occurrence of `search` in the text of a cell as
+/// `«match»`, the table has no other way to highlight part of a cell.
+pub fn highlight_match(text: &str, search: &str) -> String {
+    let lower_text = text.to_lowercase();
+    let lower_search = search.to_lowercase();
+    // the offsets found in the lower-case text only fit the text when lowering kept
+    // the length, which is not the case for a few non-ascii letters
+    if lower_search.is_empty() || lower_text.len() != text.len() {
+        return text.to_string();
+    }
+
+    let mut highlighted = String::with_capacity(text.len() + 4);
+    let mut last = 0;
+    for (start, _) in lower_text.match_indices(&lower_search) {
+        let end = start + lower_search.len();
+        if !text.is_char_boundary(start) || !text.is_char_boundary(end) {
+            continue;
+        }
+        highlighted.push_str(&text[last..start]);
+        highlighted.push('«');
+        highlighted.push_str(&text[start..end]);
+        highlighted.push('»');
+        last = end;
+    }
+    highlighted.push_str(&text[last..]);
+    highlighted
+}
+
+// The placeholder for a value compared with a column. Values are passed as text so
+// they are cast to the type of the column, a wider type such as DOUBLE would round
+// BIGINT, HUGEINT and DECIMAL values
+fn typed_placeholder(column: &ColumnSchema) -> String {
+    match column.kind {
+        ColumnKind::Number | ColumnKind::Date | ColumnKind::Timestamp => {
+            format!("CAST(? AS {})", column.sql_type)
+        }
+        ColumnKind::Text | ColumnKind::Other => "?".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> Vec<ColumnSchema> {
+        vec![
+            ColumnSchema {
+                name: "name".to_string(),
+                kind: ColumnKind::Text,
+                sql_type: "VARCHAR".to_string(),
+            },
+            ColumnSchema {
+                name: "price".to_string(),
+                kind: ColumnKind::Number,
+                sql_type: "DECIMAL(18,3)".to_string(),
+            },
+            ColumnSchema {
+                name: "sold \"on\"".to_string(),
+                kind: ColumnKind::Date,
+                sql_type: "DATE".to_string(),
+            },
+        ]
+    }
+
+    fn filter(column: usize, operator: FilterOperator, value: &str, value2: &str) -> ColumnFilter {
+        ColumnFilter {
+            column,
+            operator,
+            value: value.to_string(),
+            value2: value2.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_no_filters() {
+        let (clause, params) = where_clause(&schema(), &[], "").unwrap();
+        assert_eq!(clause, "");
+        assert!(params.is_empty());
+    }
+
+    #[test]
+    fn test_typed_predicates() {
+        let filters = vec![
+            filter(0, FilterOperator::Contains, "o'brien", ""),
+            filter(1, FilterOperator::Between, "10", ""),
+            filter(2, FilterOperator::After, "2024-01-31", ""),
+            filter(0, FilterOperator::IsNotNull, "", ""),
+        ];
+        let (clause, params) = where_clause(&schema(), &filters, "").unwrap();
+        assert_eq!(
+            clause,
+            " WHERE (contains(lower(CAST(\"name\" AS VARCHAR)), lower(?))) \
+             AND (\"price\" >= CAST(? AS DECIMAL(18,3))) \
+             AND (\"sold \"\"on\"\"\" > CAST(? AS DATE)) \
+             AND (\"name\" IS NOT NULL)"
+        );
+        // the values are parameters, never part of the sql
+        assert_eq!(params, vec!["o'brien", "10", "2024-01-31"]);
+    }
+
+    #[test]
+    fn test_range_with_both_bounds() {
+        let filters = vec![filter(1, FilterOperator::Between, "10", "20")];
+        let (clause, params) = where_clause(&schema(), &filters, "").unwrap();
+        assert_eq!(
+            clause,
+            " WHERE (\"price\" >= CAST(? AS DECIMAL(18,3)) AND \"price\" <= CAST(? AS DECIMAL(18,3)))"
+        );
+        assert_eq!(params, vec!["10", "20"]);
+    }
+
+    #[test]
+    fn test_invalid_filters() {
+        let invalid = [
+            filter(3, FilterOperator::IsNull, "", ""),
+            filter(0, FilterOperator::Equals, "", ""),
+            filter(1, FilterOperator::Between, "", ""),
+        ];
+        for filter in invalid {
+            assert!(where_clause(&schema(), &[filter], "").is_err());
+        }
+    }
+
+    #[test]
+    fn test_search_every_column() {
+        let filters = vec![filter(1, FilterOperator::Between, "", "20")];
+        let (clause, params) = where_clause(&schema(), &filters, "acme").unwrap();
+        assert_eq!(
+            clause,
+            " WHERE (\"price\" <= CAST(? AS DECIMAL(18,3))) \
+             AND (contains(lower(CAST(\"name\" AS VARCHAR)), lower(?)) \
+             OR contains(lower(CAST(\"price\" AS VARCHAR)), lower(?)) \
+             OR contains(lower(CAST(\"sold \"\"on\"\"\" AS VARCHAR)), lower(?)))"
+        );
+        assert_eq!(params, vec!["20", "acme", "acme", "acme"]);
+    }
+
+    #[test]
+    fn test_highlight_match() {
+        assert_eq!(highlight_match("ACME Corp", "acme"), "«ACME» Corp");
+        assert_eq!(highlight_match("banana", "an"), "b«an»«an»a");
+        assert_eq!(highlight_match("Zürich", "ÜR"), "Z«ür»ich");
+        assert_eq!(highlight_match("Widgets", "acme"), "Widgets");
+        assert_eq!(highlight_match("Widgets", ""), "Widgets");
+    }
+
+    #[test]
+    fn test_operator_labels() {
+        assert_eq!(
+            FilterOperator::from_label("is not null"),
+            Some(FilterOperator::IsNotNull)
+        );
+        assert_eq!(FilterOperator::from_label("like"), None);
+    }
+}
@@ -1,26 +1,44 @@
 use crate::AppWindow;
+use crate::FilterItem;
 use crate::GlobalState;
+use crate::SortItem;
 use base64::{engine::general_purpose, Engine as _};
-use duckdb::types::Value;
-use duckdb::Connection;
+use duckdb::params_from_iter;
+use duckdb::types::{Value, ValueRef};
 //use native_dialog::{MessageDialog, MessageType};
 use slint::ComponentHandle;
-use slint::{ModelRc, SharedString, StandardListViewItem, TableColumn, VecModel};
-
-use crate::model::{Filename, PageNumber, PageSize, QueryResult, SortIndex, SortOrder};
-use crate::utils::{date32_to_ymd, get_file_extension, timeunit_to_hms, timeunit_to_ymd_hms};
+use slint::{Model, ModelRc, SharedString, StandardListViewItem, TableColumn, VecModel};
+
+use crate::csv_options::{format_column_types, parse_column_types, CsvOptions};
+use crate::error::{error_details, FailedQuery, LoaderError};
+use crate::filter::{highlight_match, where_clause, ColumnFilter};
+use crate::model::{
+    ColumnKind, ColumnSchema, Filename, PageNumber, PageSize, QueryResult, SortDirection, SortKey,
+    SortSpec, TableView,
+};
+use crate::request::RequestTicket;
+use crate::session::FileSession;
+use crate::settings::{parse_threads, DuckDbSettings};
+use crate::sql::quote_identifier;
+use crate::utils::{
+    date32_to_ymd, is_csv_file, is_parquet_path, timeunit_to_hms, timeunit_to_ymd_hms,
+};
+use crate::worker::{Loader, LoaderResponse, SourceList};
 use anyhow::{Context, Error};
 use std::time::Instant;
 
-/// Retrieves, processes, and returns data from a parquet file with pagination and sorting capabilities.
+/// Retrieves, processes, and returns a page of data from an opened file with pagination and sorting capabilities.
 ///
 /// # Arguments
 ///
-/// * `filename` - The path to the parquet file to be read
+/// * `session` - The opened file to read from, narrowed by its filters and search. Caches
+///   the total row count on the first call. Search matches are marked in the cells
 /// * `page_number` - The page number to retrieve (1-indexed)
 /// * `page_size` - The number of records per page
-/// * `sort_index` - The column index to sort by (1-indexed, or -1 for no sorting)
-/// * `sort_order` - Sort in ascending (1) or descending (2) order or unsorted (0)
+/// * `sort` - The columns to sort by in order of priority, empty for no sorting. Ties are
+///   broken by the order the rows are read in, or by the other columns for a query from
+///   the sql editor, so consecutive pages never overlap. The sort direction
+///   and priority are shown in the returned column headers
 ///
 /// # Returns
 ///
@@ -31,70 +49,70 @@ use std::time::Instant;
 ///
 /// Returns an error if:
 /// * Page number is less than 1
-/// * Sort index is 0 (must be greater than 0 for columns or -1 for no sorting)
-/// * DuckDB connection fails
+/// * A filter has no value or refers to a column that doesn't exist
+/// * A sort key refers to a column that doesn't exist
 /// * Query preparation or execution fails
-/// * Column information retrieval fails
 ///
 /// # Example
 ///
 /// ```
+/// let mut session = FileSession::open(Filename("data.parquet".into()), &CsvOptions::default(), &DuckDbSettings::default())?;
 /// let result = fetch_data(
-///     Filename("data.parquet".into()),
+///     &mut session,
 ///     PageNumber(1),
 ///     PageSize(10),
-///     SortIndex(1),
-///     SortOrder(1)
+///     SortSpec(vec![SortKey { column: 0, direction: SortDirection::Ascending }]),
 /// )?;
 /// ```
 pub fn fetch_data(
-    filename: Filename,
+    session: &mut FileSession,
     page_number: PageNumber,
     page_size: PageSize,
-    sort_index: SortIndex,
-    sort_order: SortOrder,
+    sort: SortSpec,
 ) -> Result<QueryResult, Error> {
     if page_number.0 < 1 {
         return Err(Error::msg("Page number must be greater than 0"));
     }
 
-    // Create or get the DuckDB connection
-    let conn = Connection::open_in_memory()
-        .with_context(|| format!("Failed to set up duckdb connection"))?;
-
-    // Build the SQL query with sorting and filtering
-    // Get the file extension and determine the appropriate scan function
-    let extension = get_file_extension(&filename.0);
-    let scan_function = match extension.as_str() {
-        "parquet" => "parquet_scan",
-        "csv" => "read_csv_auto",
-        _ => {
-            return Err(Error::msg("Unsupported or unknown file type"));
+    // the filter bar predicates and the search, with their values passed as parameters
+    let (filter_clause, params) =
+        where_clause(session.schema(), session.filters(), session.search())?;
+
+    // total row count. The count scans the whole file so it is only run for the
+    // first page of a session and cached afterwards
+    let (row_count, row_count_cached) = match session.row_count() {
+        Some(row_count) => (row_count, true),
+        None => {
+            let row_count = count_rows(session, &filter_clause, &params)?;
+            session.set_row_count(row_count);
+            (row_count, false)
         }
     };
 
-    let mut query = format!("SELECT * FROM {}('{}')", scan_function, filename.0);
+    // reuse the connection and scan expression of the opened file
+    let conn = session.conn();
+    let filename = &session.filename;
 
-    // second query is needed to get the column names and types
-    let query2 = format!("SELECT * FROM {}('{}') LIMIT 1", scan_function, filename.0);
+    // number the rows of a sorted file or table to break ties in the sort keys. The
+    // extra columns come last and are not read
+    let numbered = if sort.0.is_empty() {
+        None
+    } else {
+        session.numbered_source()
+    };
+    let source = match &numbered {
+        Some(numbered) => numbered.source.clone(),
+        None => session.source().to_string(),
+    };
 
-    // third query is needed to get the total number of rows
-    let query3 = format!(
-        "SELECT count(1) count FROM {}('{}')",
-        scan_function, filename.0
-    );
+    // Build the SQL query with sorting and filtering
+    let mut query = format!("SELECT * FROM {}{}", source, filter_clause);
 
     // Add sorting if needed
-    let sort_direction: &str = match sort_order.0 {
-        1 => "ASC",  // ascending
-        2 => "DESC", // descending
-        _ => "",     // unsorted
-    };
-
-    // we won't do sorting if the index value is invaild
-    if sort_index.0 > 0 {
-        query.push_str(&format!(" ORDER BY {} {}", sort_index.0, sort_direction));
-    }
+    let row_number = numbered
+        .as_ref()
+        .map(|numbered| numbered.columns.as_slice());
+    query.push_str(&order_by_clause(session.schema(), &sort, row_number)?);
 
     // Add pagination
     let offset = (page_number.0 - 1) * page_size.0;
@@ -103,59 +121,30 @@ pub fn fetch_data(
     let start = Instant::now();
 
     // Execute the query
-    let mut stmt = conn
-        .prepare(&query)
-        .with_context(|| format!("Failed to create context with '{}'", filename.0))?;
-
-    // second statement
-    let mut stmt2 = conn
-        .prepare(&query2)
-        .with_context(|| format!("Failed to create metadata context with '{}'", filename.0))?;
+    let mut stmt = conn.prepare(&query).with_context(|| {
+        FailedQuery::new(
+            format!("Failed to create context with '{}'", filename.0),
+            &query,
+        )
+    })?;
 
     let rows = &mut stmt
-        .query([])
-        .with_context(|| format!("Failed to execute query"))?;
-
-    let _ = stmt2
-        .query([])
-        .with_context(|| format!("Failed to execute metadata query"))?;
-
-    // get the column count from the second statement. We can't use the first statement because the let rows =... takes
-    // ownership of it.
-    let column_count = stmt2.column_count();
-
-    // get the column metadata
-    let mut column_names: Vec<TableColumn> = Vec::new();
-    // get the column names and types
-    for i in 0..column_count {
-        // get the column name and type
-        let column_name = stmt2
-            .column_name(i)
-            .with_context(|| format!("Failed to get the column name at index '{}'", i))?
-            .to_string()
-            .clone();
-
-        let column_type = stmt2
-            .column_type(i)
-            .to_string()
-            .split('(')
-            .next()
-            .unwrap_or("")
-            .trim()
-            .to_string();
-
-        let display_name = format!("{}\n({})", column_name, column_type);
-        let mut table_column = TableColumn::default();
-        table_column.title = SharedString::from(display_name.as_str());
-        table_column.min_width = 50.0;
-        table_column.width = 100.0;
-        column_names.push(table_column);
-    }
+        .query(params_from_iter(params.iter()))
+        .with_context(|| FailedQuery::new("Failed to execute query", &query))?;
+
+    // the schema is read once when the file is opened
+    let column_names = sorted_columns(session.columns(), &sort);
+    let column_count = column_names.len();
+    let search = session.search();
 
     // get the data from the query
     let mut row_list: Vec<Vec<StandardListViewItem>> = Vec::new();
 
-    while let Some(row) = rows.next().with_context(|| format!("Failed to get row"))? {
+    // csv and json rows are parsed while they are fetched
+    while let Some(row) = rows
+        .next()
+        .with_context(|| FailedQuery::new("Failed to get row", &query))?
+    {
         // get the items from each row
         let mut row_data: Vec<StandardListViewItem> = Vec::new();
         for i in 0..column_count {
@@ -205,6 +194,12 @@ pub fn fetch_data(
 
                 Err(e) => format!("Error: {}", e),
             };
+            // show where the search matched, NULL never matches
+            let value = match row.get_ref(i) {
+                Ok(ValueRef::Null) => value,
+                _ if search.is_empty() => value,
+                _ => highlight_match(&value, search),
+            };
             row_data.push(StandardListViewItem::from(value.as_str()));
         }
         row_list.push(row_data);
@@ -212,68 +207,408 @@ pub fn fetch_data(
 
     let duration = start.elapsed();
 
-    // total row count
-    let mut stmt3 = conn
-        .prepare(&query3)
-        .with_context(|| format!("Failed to create rowcount context with '{}'", filename.0))?;
-
-    let rows = &mut stmt3
-        .query([])
-        .with_context(|| format!("Failed to execute query"))?;
-
-    let row_count = match rows.next().with_context(|| format!("Failed to get row"))? {
-        Some(row) => {
-            let row_count = row
-                .get(0)
-                .with_context(|| format!("Failed to get row count"))?;
-            row_count
-        }
-        None => -1,
-    };
-
     Ok(QueryResult {
         column_names,
+        schema: session.schema().to_vec(),
         rows: row_list,
         row_count: row_count,
+        row_count_cached: row_count_cached,
         duration: duration,
     })
 }
 
-pub fn update_table_async(
+// The ORDER BY clause for `sort`, or an empty string when the table is unsorted.
+//
+// Columns are referenced by name so the sort doesn't depend on their position. The
+// `row_number` columns break ties in the sort keys, which makes the order total and
+// every page of a sorted table read the same rows each time. Without them the remaining
+// columns break the ties. Rows equal in every column are interchangeable, so their
+// order doesn't matter
+fn order_by_clause(
+    schema: &[ColumnSchema],
+    sort: &SortSpec,
+    row_number: Option<&[&str]>,
+) -> Result<String, Error> {
+    if sort.0.is_empty() {
+        return Ok(String::new());
+    }
+
+    let mut keys = Vec::new();
+    for key in &sort.0 {
+        let column = schema
+            .get(key.column)
+            .ok_or_else(|| Error::msg(format!("No column at index {} to sort on", key.column)))?;
+        let direction = match key.direction {
+            SortDirection::Ascending => "ASC",
+            SortDirection::Descending => "DESC",
+        };
+        keys.push(format!("{} {}", quote_identifier(&column.name), direction));
+    }
+    match row_number {
+        Some(row_number) => {
+            for column in row_number {
+                keys.push(format!("{} ASC", quote_identifier(column)));
+            }
+        }
+        None => {
+            for (index, column) in schema.iter().enumerate() {
+                if sort.priority(index).is_none() {
+                    keys.push(format!("{} ASC", quote_identifier(&column.name)));
+                }
+            }
+        }
+    }
+
+    Ok(format!(" ORDER BY {}", keys.join(", ")))
+}
+
+// The table headers with the direction of each sorted column and, when more than one
+// column is sorted, its priority, e.g. `Symbol #1`.
+fn sorted_columns(columns: &[TableColumn], sort: &SortSpec) -> Vec<TableColumn> {
+    columns
+        .iter()
+        .enumerate()
+        .map(|(index, column)| {
+            let mut column = column.clone();
+            column.sort_order = header_sort_order(sort, index);
+            if let (Some(priority), true) = (sort.priority(index), sort.0.len() > 1) {
+                // the priority goes after the name, before the type on the next line
+                let title = column.title.as_str();
+                let (name, rest) = title.split_at(title.find('\n').unwrap_or(title.len()));
+                column.title = format!("{} #{}{}", name, priority, rest).into();
+            }
+            column
+        })
+        .collect()
+}
+
+// The sort arrow shown in the header of `column`.
+fn header_sort_order(sort: &SortSpec, column: usize) -> slint::SortOrder {
+    match sort.0.iter().find(|key| key.column == column) {
+        Some(key) if key.direction == SortDirection::Descending => slint::SortOrder::Descending,
+        Some(_) => slint::SortOrder::Ascending,
+        None => slint::SortOrder::Unsorted,
+    }
+}
+
+// Runs a count(1) over the whole source of the session, narrowed by the filters.
+fn count_rows(session: &FileSession, filter_clause: &str, params: &[String]) -> Result<i32, Error> {
+    let query = format!(
+        "SELECT count(1) count FROM {}{}",
+        session.source(),
+        filter_clause
+    );
+
+    let mut stmt = session.conn().prepare(&query).with_context(|| {
+        FailedQuery::new(
+            format!(
+                "Failed to create rowcount context with '{}'",
+                session.filename.0
+            ),
+            &query,
+        )
+    })?;
+
+    let rows = &mut stmt
+        .query(params_from_iter(params.iter()))
+        .with_context(|| FailedQuery::new("Failed to execute query", &query))?;
+
+    let row_count = match rows
+        .next()
+        .with_context(|| FailedQuery::new("Failed to get row", &query))?
+    {
+        Some(row) => row
+            .get(0)
+            .with_context(|| format!("Failed to get row count"))?,
+        None => -1,
+    };
+
+    Ok(row_count)
+}
+
+/// Loads a page of the opened file on the loader thread and shows it on the ui.
+///
+/// Nothing is applied to the ui if `ticket` has been superseded by a newer request
+/// by the time the page is loaded.
+pub async fn update_table_async(
     ui: &slint::Weak<AppWindow>,
-    load_table_columns: bool,
-    filename: Filename,
+    loader: &Loader,
+    ticket: RequestTicket,
     page_number: PageNumber,
     page_size: PageSize,
-    sort_index: SortIndex,
-    sort_order: SortOrder,
+    sort: SortSpec,
+) -> Result<(), Error> {
+    let response = loader
+        .page(ticket.clone(), page_number, PageSize(page_size.0), sort)
+        .await;
+    apply_response(ui, ticket, false, page_size, response)
+}
+
+/// Opens `filename` on the loader thread and shows its first page of `page_size` rows.
+/// Csv files are read with `csv_options`, and the import options page is shown if
+/// that fails.
+///
+/// Any previously opened file is closed first since the ui has already been reset.
+pub async fn open_file_async(
+    ui: &slint::Weak<AppWindow>,
+    loader: &Loader,
+    ticket: RequestTicket,
+    filename: Filename,
+    csv_options: CsvOptions,
+    page_size: PageSize,
+) -> Result<(), Error> {
+    let response = loader
+        .open(ticket.clone(), filename, csv_options, PageSize(page_size.0))
+        .await;
+    apply_response(ui, ticket, true, page_size, response)
+}
+
+/// Switches to the table at `index` of the opened database file and shows its first
+/// page of `page_size` rows.
+pub async fn select_table_async(
+    ui: &slint::Weak<AppWindow>,
+    loader: &Loader,
+    ticket: RequestTicket,
+    index: usize,
+    page_size: PageSize,
+) -> Result<(), Error> {
+    let response = loader
+        .select_table(ticket.clone(), index, PageSize(page_size.0))
+        .await;
+    apply_response(ui, ticket, true, page_size, response)
+}
+
+/// Switches to the sheet at `index` of the opened workbook, reading the column names
+/// from `header_row`, and shows its first page of `page_size` rows.
+pub async fn select_sheet_async(
+    ui: &slint::Weak<AppWindow>,
+    loader: &Loader,
+    ticket: RequestTicket,
+    index: usize,
+    header_row: u32,
+    page_size: PageSize,
+) -> Result<(), Error> {
+    let response = loader
+        .select_sheet(ticket.clone(), index, header_row, PageSize(page_size.0))
+        .await;
+    apply_response(ui, ticket, true, page_size, response)
+}
+
+/// Applies the filter bar predicates to the opened file and shows the first page of the
+/// filtered rows with the same page size and sort.
+///
+/// Returns true if the rows of `filters` are shown. The opened file keeps its previous
+/// filters when the query is cancelled or superseded.
+pub async fn filter_table_async(
+    ui: &slint::Weak<AppWindow>,
+    loader: &Loader,
+    ticket: RequestTicket,
+    filters: Vec<ColumnFilter>,
+    page_size: PageSize,
+    sort: SortSpec,
+) -> Result<bool, Error> {
+    let response = loader
+        .filter(ticket.clone(), filters, PageSize(page_size.0), sort)
+        .await;
+    let shown = matches!(response, Ok(LoaderResponse::Page { .. }));
+    apply_response(ui, ticket, false, page_size, response)?;
+    Ok(shown)
+}
+
+/// Shows the first page of the rows of the opened file that mention `search` in any
+/// column, with the same page size and sort.
+///
+/// The search box goes back to the search the table shows when the query fails or is
+/// cancelled, since the opened file keeps its previous search.
+pub async fn search_table_async(
+    ui: &slint::Weak<AppWindow>,
+    loader: &Loader,
+    ticket: RequestTicket,
+    search: String,
+    page_size: PageSize,
+    sort: SortSpec,
+) -> Result<(), Error> {
+    let response = loader
+        .search(ticket.clone(), search.clone(), PageSize(page_size.0), sort)
+        .await;
+    let shown = matches!(response, Ok(LoaderResponse::Page { .. }));
+    let current = ticket.clone();
+    let _ = ui.upgrade_in_event_loop(move |handle| {
+        // a newer request sets the search box
+        if !current.is_current() {
+            return;
+        }
+        let state = handle.global::<GlobalState>();
+        if shown {
+            state.set_shown_search(SharedString::from(search));
+        } else {
+            state.set_search_text(state.get_shown_search());
+        }
+    });
+    apply_response(ui, ticket, false, page_size, response)
+}
+
+/// Runs `sql` over the `data` view of the opened file, or goes back to the opened file
+/// when `sql` is `None`, and shows the first page of `page_size` rows of the result.
+///
+/// Returns true if the result is shown. The opened file keeps its previous query when
+/// the query fails or is cancelled.
+pub async fn run_query_async(
+    ui: &slint::Weak<AppWindow>,
+    loader: &Loader,
+    ticket: RequestTicket,
+    sql: Option<String>,
+    page_size: PageSize,
+) -> Result<bool, Error> {
+    let response = loader
+        .run_query(ticket.clone(), sql, PageSize(page_size.0))
+        .await;
+    let shown = matches!(response, Ok(LoaderResponse::Page { .. }));
+    apply_response(ui, ticket, true, page_size, response)?;
+    Ok(shown)
+}
+
+/// Runs the csv sniffer on the opened csv file and shows the detected dialect, the
+/// inferred column types and the equivalent `read_csv` call on the file info page.
+pub async fn sniff_csv_async(ui: &slint::Weak<AppWindow>, loader: &Loader) {
+    let response = loader.sniff_csv().await;
+    let _ = ui.upgrade_in_event_loop(move |handle| {
+        let state = handle.global::<GlobalState>();
+        state.set_file_info_loading(false);
+        let dialect = match response {
+            Ok(dialect) => dialect,
+            Err(e) => {
+                state.set_file_info_error(SharedString::from(e.to_string()));
+                return;
+            }
+        };
+
+        let settings = [
+            // a tab delimiter is shown the way it is typed in the import options
+            ("Delimiter", dialect.delimiter.replace('\t', "\\t")),
+            ("Quote", dialect.quote),
+            ("Escape", dialect.escape),
+            ("New line", dialect.new_line),
+            ("Skipped rows", dialect.skip_rows),
+            (
+                "Header",
+                if dialect.has_header { "Yes" } else { "No" }.to_string(),
+            ),
+            ("Date format", dialect.date_format),
+            ("Timestamp format", dialect.timestamp_format),
+        ];
+        let rows: Vec<ModelRc<StandardListViewItem>> = settings
+            .iter()
+            .map(|(name, value)| table_row(&[*name, value.as_str()]))
+            .collect();
+        state.set_csv_dialect(ModelRc::new(VecModel::from(rows)));
+
+        let rows: Vec<ModelRc<StandardListViewItem>> = dialect
+            .columns
+            .iter()
+            .map(|(name, column_type)| table_row(&[name.as_str(), column_type.as_str()]))
+            .collect();
+        state.set_csv_columns(ModelRc::new(VecModel::from(rows)));
+        state.set_csv_read_csv(SharedString::from(dialect.read_csv));
+    });
+}
+
+/// Reads the metadata of the opened parquet file or dataset and shows it in the
+/// tables of the inspect page.
+pub async fn inspect_parquet_async(ui: &slint::Weak<AppWindow>, loader: &Loader) {
+    let response = loader.inspect_parquet().await;
+    let _ = ui.upgrade_in_event_loop(move |handle| {
+        let state = handle.global::<GlobalState>();
+        state.set_inspect_loading(false);
+        let metadata = match response {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                state.set_inspect_error(SharedString::from(e.to_string()));
+                return;
+            }
+        };
+
+        state.set_parquet_files(table_rows(&metadata.files));
+        state.set_parquet_key_values(table_rows(&metadata.key_values));
+        state.set_parquet_schema(table_rows(&metadata.schema));
+        state.set_parquet_column_chunks(table_rows(&metadata.column_chunks));
+    });
+}
+
+// The rows of a StandardTableView with the given cell texts.
+fn table_rows(rows: &[Vec<String>]) -> ModelRc<ModelRc<StandardListViewItem>> {
+    let rows: Vec<ModelRc<StandardListViewItem>> = rows
+        .iter()
+        .map(|row| {
+            let cells: Vec<&str> = row.iter().map(|cell| cell.as_str()).collect();
+            table_row(&cells)
+        })
+        .collect();
+    ModelRc::new(VecModel::from(rows))
+}
+
+// A row of a StandardTableView with the given cell texts.
+fn table_row(cells: &[&str]) -> ModelRc<StandardListViewItem> {
+    let items: Vec<StandardListViewItem> = cells
+        .iter()
+        .map(|cell| StandardListViewItem::from(*cell))
+        .collect();
+    ModelRc::new(VecModel::from(items))
+}
+
+// Shows the outcome of a loader request on the ui.
+fn apply_response(
+    ui: &slint::Weak<AppWindow>,
+    ticket: RequestTicket,
+    load_table_columns: bool,
+    page_size: PageSize,
+    response: Result<LoaderResponse, Error>,
 ) -> Result<(), Error> {
-    let filename_clone = filename.clone();
-    // fetch the data
-    match fetch_data(
-        filename,
-        PageNumber(page_number.0),
-        PageSize(page_size.0),
-        SortIndex(sort_index.0),
-        SortOrder(sort_order.0),
-    ) {
-        Ok(results) => {
+    match response {
+        Ok(LoaderResponse::Page {
+            results,
+            filename,
+            sources,
+        }) => {
             let ui_clone = ui.clone();
             update_table_ui(
                 ui_clone,
+                ticket.clone(),
                 load_table_columns,
                 results,
                 page_size,
-                filename_clone,
+                filename,
+                sources,
             );
-            stop_page_loading(ui.clone());
+            stop_page_loading(ui.clone(), ticket);
+            Ok(())
+        }
+        Ok(LoaderResponse::Cancelled(previous)) => {
+            stop_page_loading(ui.clone(), ticket.clone());
+            if let Some(previous) = previous {
+                restore_table_view(ui.clone(), ticket, previous);
+            }
+            Ok(())
+        }
+        Ok(LoaderResponse::Failed { error, shown }) => {
+            stop_page_loading(ui.clone(), ticket.clone());
+            // the page and sort were reset on the ui when the request was made
+            if let Some(shown) = shown {
+                restore_table_view(ui.clone(), ticket, shown);
+            }
+            Err(error)
+        }
+        // the newer request updates the ui
+        Ok(LoaderResponse::Superseded) => Ok(()),
+        Ok(LoaderResponse::CsvOptionsRequired { filename, message }) => {
+            stop_page_loading(ui.clone(), ticket.clone());
+            show_csv_options(ui.clone(), ticket, filename, message);
             Ok(())
         }
-        Err(_e) => {
+        Err(e) => {
             let ui_clone = ui.clone();
-            stop_page_loading(ui_clone);
-            let error_message: String = format!("Error reading file '{}'", &filename_clone.0);
-            Err(Error::msg(error_message))
+            stop_page_loading(ui_clone, ticket);
+            Err(e)
         }
     }
 }
@@ -284,13 +619,44 @@ pub fn set_ui_defaults(ui: &AppWindow) {
         .set_filename(SharedString::from(""));
     ui.global::<GlobalState>().set_pagination_enabled(false);
     ui.global::<GlobalState>().set_record_count(10);
-    ui.global::<GlobalState>().set_sort_index(-1);
-    ui.global::<GlobalState>().set_sort_order(0);
+    set_sort_ui(ui, &SortSpec::default());
     ui.global::<GlobalState>().set_max_pages(1);
     ui.global::<GlobalState>().set_page_number(1);
+    ui.global::<GlobalState>()
+        .set_error_title(SharedString::from(""));
     ui.global::<GlobalState>()
         .set_error_message(SharedString::from(""));
     ui.global::<GlobalState>().set_has_error(false);
+    ui.global::<GlobalState>()
+        .set_error_details(SharedString::from(""));
+    ui.global::<GlobalState>().set_error_panel_visible(false);
+
+    // clear the table picker of a previously opened database
+    let tables: Vec<StandardListViewItem> = Vec::new();
+    ui.global::<GlobalState>()
+        .set_tables(ModelRc::new(VecModel::from(tables)));
+    ui.global::<GlobalState>().set_table_index(0);
+    ui.global::<GlobalState>().set_is_workbook(false);
+    ui.global::<GlobalState>().set_header_row(1);
+    ui.global::<GlobalState>().set_is_csv(false);
+    ui.global::<GlobalState>().set_is_parquet(false);
+    ui.global::<GlobalState>().set_query_active(false);
+
+    // the filter bar is filled once the columns of the new file are known
+    let filter_columns: Vec<SharedString> = Vec::new();
+    ui.global::<GlobalState>()
+        .set_filter_columns(ModelRc::new(VecModel::from(filter_columns)));
+    ui.global::<GlobalState>()
+        .set_search_text(SharedString::from(""));
+    ui.global::<GlobalState>()
+        .set_shown_search(SharedString::from(""));
+    let filters: Vec<FilterItem> = Vec::new();
+    ui.global::<GlobalState>()
+        .set_filters(ModelRc::new(VecModel::from(filters)));
+    ui.global::<GlobalState>()
+        .set_sql_error(SharedString::from(""));
+    ui.global::<GlobalState>()
+        .set_csv_error(SharedString::from(""));
 
     // clear the table headers
     let column_names: Vec<TableColumn> = Vec::new();
@@ -304,35 +670,305 @@ pub fn set_ui_defaults(ui: &AppWindow) {
         .set_row_data(ModelRc::new(VecModel::from(model_data)));
 }
 
-fn stop_page_loading(ui: slint::Weak<AppWindow>) {
+/// Shows `csv_options` in the fields of the csv import options page.
+pub fn set_csv_options_ui(ui: &AppWindow, csv_options: &CsvOptions) {
+    let state = ui.global::<GlobalState>();
+    state.set_csv_delimiter(csv_options.delimiter.as_str().into());
+    state.set_csv_quote(csv_options.quote.as_str().into());
+    state.set_csv_escape(csv_options.escape.as_str().into());
+    state.set_csv_encoding(csv_options.encoding.as_str().into());
+    // 0 lets the sniffer decide, 1 for a header row and 2 for none
+    state.set_csv_header(match csv_options.header {
+        None => 0,
+        Some(true) => 1,
+        Some(false) => 2,
+    });
+    state.set_csv_skip_rows(csv_options.skip_rows as i32);
+    state.set_csv_date_format(csv_options.date_format.as_str().into());
+    state.set_csv_timestamp_format(csv_options.timestamp_format.as_str().into());
+    state.set_csv_null_string(csv_options.null_string.as_str().into());
+    state.set_csv_column_types(format_column_types(&csv_options.column_types).into());
+}
+
+/// Shows `sort` as the sort stack of the table, which header clicks add to.
+pub fn set_sort_ui(ui: &AppWindow, sort: &SortSpec) {
+    let items: Vec<SortItem> = sort
+        .0
+        .iter()
+        .map(|key| SortItem {
+            column: key.column as i32,
+            descending: key.direction == SortDirection::Descending,
+        })
+        .collect();
+    ui.global::<GlobalState>()
+        .set_sort_keys(ModelRc::new(VecModel::from(items)));
+}
+
+/// Reads the sort stack of the table, the first column has the highest priority.
+pub fn sort_from_ui(ui: &AppWindow) -> SortSpec {
+    let keys = ui
+        .global::<GlobalState>()
+        .get_sort_keys()
+        .iter()
+        .filter_map(|item| {
+            Some(SortKey {
+                column: usize::try_from(item.column).ok()?,
+                direction: if item.descending {
+                    SortDirection::Descending
+                } else {
+                    SortDirection::Ascending
+                },
+            })
+        })
+        .collect();
+    SortSpec(keys)
+}
+
+/// Reads the csv import options from the fields of the options page.
+///
+/// # Errors
+///
+/// Returns an error if the column type overrides are not written as `column:TYPE` pairs
+pub fn csv_options_from_ui(ui: &AppWindow) -> Result<CsvOptions, Error> {
+    let state = ui.global::<GlobalState>();
+    Ok(CsvOptions {
+        delimiter: state.get_csv_delimiter().to_string(),
+        quote: state.get_csv_quote().to_string(),
+        escape: state.get_csv_escape().to_string(),
+        encoding: state.get_csv_encoding().to_string(),
+        header: match state.get_csv_header() {
+            1 => Some(true),
+            2 => Some(false),
+            _ => None,
+        },
+        skip_rows: state.get_csv_skip_rows().max(0) as u32,
+        date_format: state.get_csv_date_format().to_string(),
+        timestamp_format: state.get_csv_timestamp_format().to_string(),
+        null_string: state.get_csv_null_string().to_string(),
+        column_types: parse_column_types(&state.get_csv_column_types())?,
+    })
+}
+
+/// Shows `settings` on the settings page along with the values DuckDB uses for
+/// them, or why DuckDB rejects them.
+pub fn set_settings_ui(ui: &AppWindow, settings: &DuckDbSettings) {
+    let state = ui.global::<GlobalState>();
+    state.set_settings_memory_limit(settings.memory_limit.as_str().into());
+    state.set_settings_threads(if settings.threads > 0 {
+        settings.threads.to_string().into()
+    } else {
+        SharedString::new()
+    });
+    state.set_settings_temp_directory(settings.temp_directory.as_str().into());
+    match settings.effective() {
+        Ok(effective) => {
+            state.set_settings_error(SharedString::new());
+            state.set_duckdb_settings(
+                format!(
+                    "Memory limit: {}\nThreads: {}\nTemp directory: {}",
+                    effective.memory_limit,
+                    effective.threads,
+                    if effective.temp_directory.is_empty() {
+                        "none"
+                    } else {
+                        effective.temp_directory.as_str()
+                    }
+                )
+                .into(),
+            );
+        }
+        Err(e) => state.set_settings_error(format!("{:#}", e).into()),
+    }
+}
+
+/// Shows why the settings file could not be read, on the settings page and in the error
+/// panel. The default settings are used until the settings are saved again.
+pub fn show_settings_file_error(ui: &AppWindow, error: &Error) {
+    let state = ui.global::<GlobalState>();
+    state.set_settings_error(format!("{:#}", error).into());
+    state.set_error_title(SharedString::from("Invalid Settings"));
+    state.set_error_message(SharedString::from(error.to_string()));
+    state.set_error_details(SharedString::from(format!("{:?}", error)));
+    state.set_error_panel_visible(true);
+}
+
+/// Reads the DuckDB settings from the settings page, an empty thread count uses
+/// DuckDB's default.
+///
+/// # Errors
+///
+/// Returns an error if the thread count is not a number
+pub fn settings_from_ui(ui: &AppWindow) -> Result<DuckDbSettings, Error> {
+    let state = ui.global::<GlobalState>();
+    Ok(DuckDbSettings {
+        memory_limit: state.get_settings_memory_limit().trim().to_string(),
+        threads: parse_threads(&state.get_settings_threads())?,
+        temp_directory: state.get_settings_temp_directory().trim().to_string(),
+    })
+}
+
+/// Shows `error` titled by its kind. The viewer keeps working after most errors, so
+/// they are shown in the error panel above the page along with the details to copy.
+/// A stopped loader is shown in the error dialog. A cancelled query is not an error,
+/// the table keeps the rows it shows.
+pub fn show_error(ui: &slint::Weak<AppWindow>, error: Error) {
+    let loader_error = LoaderError::of(&error);
+    if loader_error == LoaderError::Cancelled {
+        return;
+    }
+    let _ = ui.upgrade_in_event_loop(move |handle| {
+        let state = handle.global::<GlobalState>();
+        state.set_error_title(SharedString::from(loader_error.title()));
+        state.set_error_message(SharedString::from(loader_error.to_string()));
+        if loader_error == LoaderError::Stopped {
+            state.set_has_error(true);
+            return;
+        }
+        let details = error_details(&error, &state.get_filename());
+        state.set_error_details(SharedString::from(details));
+        state.set_error_panel_visible(true);
+    });
+}
+
+fn stop_page_loading(ui: slint::Weak<AppWindow>, ticket: RequestTicket) {
     let _ = ui.upgrade_in_event_loop(move |handle| {
+        // a newer request is still loading
+        if !ticket.is_current() {
+            return;
+        }
         handle.global::<GlobalState>().set_page_loading(false)
     });
 }
 
+// Opens the csv import options page for `filename` with the reason the file could
+// not be read.
+fn show_csv_options(
+    ui: slint::Weak<AppWindow>,
+    ticket: RequestTicket,
+    filename: Filename,
+    message: String,
+) {
+    let _ = ui.upgrade_in_event_loop(move |handle| {
+        if !ticket.is_current() {
+            return;
+        }
+        handle.global::<GlobalState>().set_csv_filename(filename.0);
+        handle
+            .global::<GlobalState>()
+            .set_csv_error(SharedString::from(message));
+        // keeps the import options in the menu after leaving the page
+        handle.global::<GlobalState>().set_is_csv(true);
+        handle.set_current_page(SharedString::from("csv_options"));
+    });
+}
+
+// Puts the page and sort on the ui back to `view` and re-enables the pagination.
+fn restore_table_view(ui: slint::Weak<AppWindow>, ticket: RequestTicket, view: TableView) {
+    let _ = ui.upgrade_in_event_loop(move |handle| {
+        if !ticket.is_current() {
+            return;
+        }
+        handle
+            .global::<GlobalState>()
+            .set_page_number(view.page_number);
+        handle.global::<GlobalState>().set_page_size(view.page_size);
+        set_sort_ui(&handle, &view.sort);
+        // the headers show the sort of the page that is still shown
+        let headers = handle.global::<GlobalState>().get_column_names();
+        for index in 0..headers.row_count() {
+            if let Some(mut column) = headers.row_data(index) {
+                column.sort_order = header_sort_order(&view.sort, index);
+                headers.set_row_data(index, column);
+            }
+        }
+        handle.global::<GlobalState>().set_pagination_enabled(true);
+    });
+}
+
 fn update_table_ui(
     ui: slint::Weak<AppWindow>,
+    ticket: RequestTicket,
     load_table_columns: bool,
     results: QueryResult,
     page_size: PageSize,
     filename: Filename,
+    sources: Option<SourceList>,
 ) {
     let _ = ui.upgrade_in_event_loop(move |handle| {
+        // checked again on the event loop since a newer request may have been
+        // made after this page was loaded
+        if !ticket.is_current() {
+            return;
+        }
         let row_count = results.row_count;
         let mut model_data = Vec::new();
         // Convert the results to the model format
         for row_result in results.rows.into_iter() {
             model_data.push(ModelRc::new(VecModel::from(row_result)));
         }
-        if load_table_columns {
-            let model_columns: ModelRc<TableColumn> =
-                ModelRc::new(VecModel::from(results.column_names.clone()));
-            // set the column header names on the ui
+        // set the column header names on the ui, and the sort shown in them. Unchanged
+        // headers are kept so the table keeps its column widths while paging
+        let headers = handle.global::<GlobalState>().get_column_names();
+        let headers_changed = headers.row_count() != results.column_names.len()
+            || headers
+                .iter()
+                .zip(&results.column_names)
+                .any(|(shown, column)| {
+                    shown.title != column.title || shown.sort_order != column.sort_order
+                });
+        if load_table_columns || headers_changed {
+            let mut columns = results.column_names.clone();
+            // a new sort keeps the widths the headers were dragged to
+            if !load_table_columns && headers.row_count() == columns.len() {
+                for (column, shown) in columns.iter_mut().zip(headers.iter()) {
+                    column.width = shown.width;
+                }
+            }
+            let model_columns: ModelRc<TableColumn> = ModelRc::new(VecModel::from(columns));
             handle
                 .global::<GlobalState>()
                 .set_column_names(model_columns);
         }
 
+        if load_table_columns {
+            // the filter bar offers the columns of the new table and starts empty, like
+            // the search box
+            let names: Vec<SharedString> = results
+                .schema
+                .iter()
+                .map(|column| SharedString::from(column.name.as_str()))
+                .collect();
+            let kinds: Vec<i32> = results
+                .schema
+                .iter()
+                .map(|column| match column.kind {
+                    ColumnKind::Text => 0,
+                    ColumnKind::Number => 1,
+                    ColumnKind::Date => 2,
+                    ColumnKind::Timestamp => 3,
+                    ColumnKind::Other => 4,
+                })
+                .collect();
+            let state = handle.global::<GlobalState>();
+            state.set_filter_columns(ModelRc::new(VecModel::from(names)));
+            state.set_filter_kinds(ModelRc::new(VecModel::from(kinds)));
+            state.set_filters(ModelRc::new(VecModel::from(Vec::<FilterItem>::new())));
+            state.set_search_text(SharedString::from(""));
+            state.set_shown_search(SharedString::from(""));
+
+            // parquet files and datasets can be inspected
+            handle
+                .global::<GlobalState>()
+                .set_is_parquet(is_parquet_path(&filename.0));
+            // the import options can be changed for an opened csv file
+            if is_csv_file(&filename.0) {
+                handle.global::<GlobalState>().set_is_csv(true);
+                handle
+                    .global::<GlobalState>()
+                    .set_csv_filename(filename.0.clone());
+            }
+        }
+
         // set the row data on the ui
         handle
             .global::<GlobalState>()
@@ -351,15 +987,38 @@ fn update_table_ui(
             .global::<GlobalState>()
             .set_duration(format!("{:?}", results.duration).into());
         handle.global::<GlobalState>().set_filename(filename.0);
+
+        // fill the picker when a database file or workbook has been opened
+        if let Some(sources) = sources {
+            let (names, is_workbook) = match sources {
+                SourceList::Tables(names) => (names, false),
+                SourceList::Sheets(names) => (names, true),
+            };
+            let items: Vec<StandardListViewItem> = names
+                .iter()
+                .map(|name| StandardListViewItem::from(name.as_str()))
+                .collect();
+            handle
+                .global::<GlobalState>()
+                .set_tables(ModelRc::new(VecModel::from(items)));
+            handle.global::<GlobalState>().set_is_workbook(is_workbook);
+        }
     });
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::filter::FilterOperator;
+    use duckdb::Connection;
+    use std::collections::HashSet;
     use std::fs;
     use std::path::Path;
 
+    fn sort_on(column: usize, direction: SortDirection) -> SortSpec {
+        SortSpec(vec![SortKey { column, direction }])
+    }
+
     // Helper function to create a test parquet file
     fn create_test_parquet_file(path: &str) -> Result<(), Error> {
         // We'll use DuckDB to create a parquet file for testing
@@ -433,6 +1092,29 @@ mod tests {
         Ok(())
     }
 
+    // Helper function to create a test duckdb database with a table and a view
+    fn create_test_database_file(path: &str) -> Result<(), Error> {
+        // start from an empty database
+        if Path::new(path).exists() {
+            fs::remove_file(path)?;
+        }
+        let conn = Connection::open(path)?;
+
+        conn.execute(
+            "CREATE TABLE products AS SELECT * FROM (VALUES
+             (1, 'Product A', 19.99),
+             (2, 'Product B', 29.99),
+             (3, 'Product C', 9.99)) t(id, name, price)",
+            [],
+        )?;
+        conn.execute(
+            "CREATE VIEW cheap_products AS SELECT id, name FROM products WHERE price < 20",
+            [],
+        )?;
+
+        Ok(())
+    }
+
     #[test]
     fn test_fetch_data_parquet() -> Result<(), Error> {
         // Create a temporary test file in the target directory
@@ -448,12 +1130,16 @@ mod tests {
         );
 
         // Test with default parameters
-        let result = fetch_data(
+        let mut session = FileSession::open(
             Filename(test_file_path.into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default(),
+        )?;
+        let result = fetch_data(
+            &mut session,
             PageNumber(1),
             PageSize(10),
-            SortIndex(-1),
-            SortOrder(1), // 1 is ascending
+            SortSpec::default(),
         )?;
 
         // Verify the results
@@ -466,6 +1152,11 @@ mod tests {
         );
         let total_rows = result.rows.len();
         assert_eq!(total_rows, 5, "Expected 5 rows but got {}", total_rows);
+        assert_eq!(result.row_count, 5);
+        assert!(
+            !result.row_count_cached,
+            "Expected the first page to count the rows"
+        );
 
         // Check column names
         // the namee construct is the ccombination of the name and the type
@@ -489,11 +1180,10 @@ mod tests {
 
         // Test pagination - page 1 with 2 items per page
         let page1 = fetch_data(
-            Filename(test_file_path.into()),
+            &mut session,
             PageNumber(1),
             PageSize(2),
-            SortIndex(-1),
-            SortOrder(1), // 1 is ascending
+            SortSpec::default(),
         )?;
         let total_rows = page1.rows.len();
         assert_eq!(
@@ -504,11 +1194,10 @@ mod tests {
 
         // Test pagination - page 2 with 2 items per page
         let page2 = fetch_data(
-            Filename(test_file_path.into()),
+            &mut session,
             PageNumber(2),
             PageSize(2),
-            SortIndex(-1),
-            SortOrder(1), // 1 is ascending
+            SortSpec::default(),
         )?;
         let total_rows = page2.rows.len();
         assert_eq!(
@@ -516,14 +1205,18 @@ mod tests {
             "Expected 2 rows in page 2 but got {}",
             total_rows
         );
+        assert_eq!(page2.row_count, 5);
+        assert!(
+            page2.row_count_cached,
+            "Expected page 2 to reuse the cached row count"
+        );
 
         // Test sorting by id in descending order (column index 0)
         let sorted = fetch_data(
-            Filename(test_file_path.into()),
+            &mut session,
             PageNumber(1),
             PageSize(10),
-            SortIndex(1),
-            SortOrder(2), // 2 is descending
+            sort_on(0, SortDirection::Descending),
         )?;
         // First row should have id = 5
         assert_eq!(
@@ -555,12 +1248,16 @@ mod tests {
         );
 
         // Test with default parameters
-        let result = fetch_data(
+        let mut session = FileSession::open(
             Filename(test_file_path.into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default(),
+        )?;
+        let result = fetch_data(
+            &mut session,
             PageNumber(1),
             PageSize(10),
-            SortIndex(-1),
-            SortOrder(1), // 1 is ascending
+            SortSpec::default(),
         )?;
 
         // Verify the results
@@ -596,11 +1293,10 @@ mod tests {
 
         // Test pagination - page 1 with 2 items per page
         let page1 = fetch_data(
-            Filename(test_file_path.into()),
+            &mut session,
             PageNumber(1),
             PageSize(2),
-            SortIndex(-1),
-            SortOrder(1), // 1 is ascending
+            SortSpec::default(),
         )?;
         let total_rows = page1.rows.len();
         assert_eq!(
@@ -611,11 +1307,10 @@ mod tests {
 
         // Test pagination - page 2 with 2 items per page
         let page2 = fetch_data(
-            Filename(test_file_path.into()),
+            &mut session,
             PageNumber(2),
             PageSize(2),
-            SortIndex(-1),
-            SortOrder(1), // 1 is ascending
+            SortSpec::default(),
         )?;
         let total_rows = page2.rows.len();
         assert_eq!(
@@ -626,11 +1321,10 @@ mod tests {
 
         // Test sorting by id in descending order (column index 0)
         let sorted = fetch_data(
-            Filename(test_file_path.into()),
+            &mut session,
             PageNumber(1),
             PageSize(10),
-            SortIndex(1),
-            SortOrder(2), // 2 is descending
+            sort_on(0, SortDirection::Descending),
         )?;
         // First row should have id = 5
         assert_eq!(
@@ -646,4 +1340,1126 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_fetch_data_json() -> Result<(), Error> {
+        // the same rows as newline-delimited json and as a json array
+        let test_files = vec![
+            "test_data/products.ndjson",
+            "test_data/products.jsonl",
+            "test_data/products.json",
+        ];
+
+        for test_file_path in test_files {
+            let mut session = FileSession::open(
+                Filename(test_file_path.into()),
+                &CsvOptions::default(),
+                &DuckDbSettings::default(),
+            )?;
+            let result = fetch_data(
+                &mut session,
+                PageNumber(1),
+                PageSize(10),
+                SortSpec::default(),
+            )?;
+
+            let column_count = result.column_names.len();
+            assert_eq!(
+                column_count, 5,
+                "Expected 5 columns in '{}' but got {}",
+                test_file_path, column_count
+            );
+            assert_eq!(result.rows.len(), 5);
+            assert_eq!(result.row_count, 5);
+
+            // Check column names
+            let expected_columns = vec![
+                "id\n(Int64)",
+                "name\n(Utf8)",
+                "category\n(Utf8)",
+                "price\n(Float64)",
+                "in_stock\n(Boolean)",
+            ];
+            for col in expected_columns {
+                let name_exists = result
+                    .column_names
+                    .iter()
+                    .any(|metadata| metadata.title.as_str() == col);
+                assert!(
+                    name_exists,
+                    "Column '{}' not found in column_names: {:?}",
+                    col, result.column_names
+                );
+            }
+
+            // Test pagination - page 3 with 2 items per page
+            let page3 = fetch_data(
+                &mut session,
+                PageNumber(3),
+                PageSize(2),
+                SortSpec::default(),
+            )?;
+            assert_eq!(page3.rows.len(), 1);
+
+            // Test sorting by price in descending order
+            let sorted = fetch_data(
+                &mut session,
+                PageNumber(1),
+                PageSize(10),
+                sort_on(3, SortDirection::Descending),
+            )?;
+            assert_eq!(sorted.rows[0][0].text, "5");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_data_database() -> Result<(), Error> {
+        let test_file_path = "target/test_data.duckdb";
+        create_test_database_file(test_file_path)?;
+
+        let mut session = FileSession::open(
+            Filename(test_file_path.into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default(),
+        )?;
+
+        // the view sorts before the table
+        let tables: Vec<String> = session
+            .tables()
+            .iter()
+            .map(|table| table.display_name())
+            .collect();
+        assert_eq!(tables, vec!["main.cheap_products (view)", "main.products"]);
+
+        // the first table is selected when the file is opened
+        let view = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            SortSpec::default(),
+        )?;
+        assert_eq!(view.column_names.len(), 2);
+        assert_eq!(view.row_count, 2);
+
+        // switch to the table and sort it by price
+        session.select_table(1)?;
+        let table = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            sort_on(2, SortDirection::Descending),
+        )?;
+        assert_eq!(table.column_names.len(), 3);
+        assert_eq!(table.row_count, 3);
+        assert!(
+            !table.row_count_cached,
+            "Expected the row count to be recomputed for the new table"
+        );
+        assert_eq!(table.rows[0][0].text, "2");
+
+        assert!(session.select_table(2).is_err());
+
+        drop(session);
+        fs::remove_file(test_file_path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs the bundled sqlite_scanner extension"]
+    fn test_fetch_data_sqlite() -> Result<(), Error> {
+        let mut session = FileSession::open(
+            Filename("test_data/products.sqlite".into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default(),
+        )?;
+        let tables: Vec<String> = session
+            .tables()
+            .iter()
+            .map(|table| table.display_name())
+            .collect();
+        assert_eq!(tables, vec!["main.electronics (view)", "main.products"]);
+
+        // the view is selected when the file is opened
+        let view = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            SortSpec::default(),
+        )?;
+        assert_eq!(view.column_names.len(), 3);
+        assert_eq!(view.row_count, 2);
+
+        session.select_table(1)?;
+        let table = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(2),
+            sort_on(3, SortDirection::Descending),
+        )?;
+        let titles: Vec<&str> = table
+            .column_names
+            .iter()
+            .map(|column| column.title.as_str())
+            .collect();
+        assert_eq!(
+            titles,
+            vec![
+                "id\n(Int64)",
+                "name\n(Utf8)",
+                "category\n(Utf8)",
+                "price\n(Float64)"
+            ]
+        );
+        assert_eq!(table.row_count, 5);
+        assert_eq!(table.rows.len(), 2);
+        assert_eq!(table.rows[0][1].text, "Product E");
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs the bundled excel extension"]
+    fn test_fetch_data_workbook() -> Result<(), Error> {
+        let mut session = FileSession::open(
+            Filename("test_data/products.xlsx".into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default(),
+        )?;
+        assert_eq!(session.sheets(), ["Products", "Summary"]);
+
+        // the first sheet opens with its first row as the header, which is a title here
+        let first = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            SortSpec::default(),
+        )?;
+        assert_eq!(first.row_count, 6);
+
+        // the column names are in the second row
+        session.select_sheet(0, 2)?;
+        let names: Vec<&str> = session
+            .schema()
+            .iter()
+            .map(|column| column.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["id", "name", "category", "price"]);
+        let page = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(2),
+            sort_on(3, SortDirection::Descending),
+        )?;
+        assert_eq!(page.row_count, 5);
+        assert_eq!(page.rows.len(), 2);
+        assert_eq!(page.rows[0][1].text, "Product E");
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs the bundled nanoarrow extension"]
+    fn test_fetch_data_arrow() -> Result<(), Error> {
+        let mut session = FileSession::open(
+            Filename("test_data/products.arrow".into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default(),
+        )?;
+        let kinds: Vec<ColumnKind> = session.schema().iter().map(|column| column.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ColumnKind::Number,
+                ColumnKind::Text,
+                ColumnKind::Text,
+                ColumnKind::Number,
+                ColumnKind::Other
+            ]
+        );
+
+        let page = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(2),
+            sort_on(3, SortDirection::Descending),
+        )?;
+        assert_eq!(page.row_count, 5);
+        assert_eq!(page.rows.len(), 2);
+        // the same arrow type names as a parquet file
+        assert_eq!(page.column_names[0].title.as_str(), "id\n(Int64)");
+        assert_eq!(page.column_names[3].title.as_str(), "price\n(Float64)");
+        assert_eq!(page.rows[0][1].text, "Product E");
+        assert_eq!(page.rows[0][3].text, "99.99");
+        assert_eq!(page.rows[0][4].text, "false");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_data_partitioned_dataset() -> Result<(), Error> {
+        let test_dir_path = "target/test_dataset";
+        if Path::new(test_dir_path).exists() {
+            fs::remove_dir_all(test_dir_path)?;
+        }
+
+        // write the test rows as a hive partitioned dataset, one folder per category
+        let conn = Connection::open_in_memory()?;
+        conn.execute(
+            &format!(
+                "COPY (SELECT * FROM (VALUES
+                 (1, 'Product A', 'Electronics', 19.99),
+                 (2, 'Product B', 'Clothing', 29.99),
+                 (3, 'Product C', 'Food', 9.99),
+                 (4, 'Product D', 'Books', 14.99),
+                 (5, 'Product E', 'Electronics', 99.99)) t(id, name, category, price))
+                 TO '{}' (FORMAT PARQUET, PARTITION_BY (category))",
+                test_dir_path
+            ),
+            [],
+        )?;
+
+        // the directory and a glob over it open the same dataset
+        let test_paths = vec![
+            test_dir_path.to_string(),
+            format!("{}/category=*/*.parquet", test_dir_path),
+        ];
+        for test_path in test_paths {
+            let mut session = FileSession::open(
+                Filename(test_path.as_str().into()),
+                &CsvOptions::default(),
+                &DuckDbSettings::default(),
+            )?;
+            let result = fetch_data(
+                &mut session,
+                PageNumber(1),
+                PageSize(10),
+                SortSpec::default(),
+            )?;
+            assert_eq!(result.column_names.len(), 4);
+            assert_eq!(result.row_count, 5);
+
+            // the partition column is marked in its header
+            let partition_exists = result
+                .column_names
+                .iter()
+                .any(|metadata| metadata.title.as_str() == "category\n(Utf8, partition)");
+            assert!(
+                partition_exists,
+                "Partition column not found in column_names: {:?}",
+                result.column_names
+            );
+
+            // page through the whole dataset sorted by id
+            let page2 = fetch_data(
+                &mut session,
+                PageNumber(2),
+                PageSize(3),
+                sort_on(0, SortDirection::Ascending),
+            )?;
+            assert_eq!(page2.rows.len(), 2);
+            assert_eq!(page2.rows[0][0].text, "4");
+
+            // the two Electronics rows are tied and keep the order of their file
+            let category = session
+                .schema()
+                .iter()
+                .position(|column| column.name == "category")
+                .expect("Expected a category column");
+            let by_category = fetch_data(
+                &mut session,
+                PageNumber(1),
+                PageSize(10),
+                sort_on(category, SortDirection::Descending),
+            )?;
+            let ids: Vec<&str> = by_category
+                .rows
+                .iter()
+                .map(|row| row[0].text.as_str())
+                .collect();
+            assert_eq!(ids, ["3", "1", "5", "2", "4"]);
+            assert!(by_category.rows.iter().all(|row| row.len() == 4));
+        }
+
+        fs::remove_dir_all(test_dir_path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_data_compressed_csv() -> Result<(), Error> {
+        let mut session = FileSession::open(
+            Filename("test_data/AAPL_minute.csv".into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default(),
+        )?;
+        let expected = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(20),
+            SortSpec::default(),
+        )?;
+
+        // write compressed copies of the csv file
+        let conn = Connection::open_in_memory()?;
+        let test_files = vec![
+            ("target/AAPL_minute.csv.gz", "gzip"),
+            ("target/AAPL_minute.csv.zst", "zstd"),
+        ];
+        for (test_file_path, compression) in &test_files {
+            conn.execute(
+                &format!(
+                    "COPY (SELECT * FROM read_csv_auto('test_data/AAPL_minute.csv')) TO '{}' (FORMAT CSV, COMPRESSION {})",
+                    test_file_path, compression
+                ),
+                [],
+            )?;
+        }
+
+        for (test_file_path, _compression) in &test_files {
+            let mut session = FileSession::open(
+                Filename((*test_file_path).into()),
+                &CsvOptions::default(),
+                &DuckDbSettings::default(),
+            )?;
+            let result = fetch_data(
+                &mut session,
+                PageNumber(1),
+                PageSize(20),
+                SortSpec::default(),
+            )?;
+
+            assert_eq!(result.column_names.len(), expected.column_names.len());
+            assert_eq!(
+                result.row_count, expected.row_count,
+                "Expected {} rows in '{}' but got {}",
+                expected.row_count, test_file_path, result.row_count
+            );
+            assert_eq!(result.rows[0][0].text, expected.rows[0][0].text);
+
+            fs::remove_file(test_file_path)?;
+        }
+
+        // parquet files are compressed internally
+        assert!(FileSession::open(
+            Filename("target/AAPL_minute.parquet.gz".into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default()
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_data_csv_options() -> Result<(), Error> {
+        // a semicolon-delimited export with a preamble above the header
+        let test_file_path = "target/test_export.csv";
+        fs::write(
+            test_file_path,
+            "Exported by Example Bank\n\
+             Account;12345\n\
+             id;name;booked\n\
+             001;Coffee;31.01.2024\n\
+             002;n/a;01.02.2024\n\
+             003;Books;n/a\n",
+        )?;
+
+        let csv_options = CsvOptions {
+            delimiter: ";".to_string(),
+            header: Some(true),
+            skip_rows: 2,
+            date_format: "%d.%m.%Y".to_string(),
+            null_string: "n/a".to_string(),
+            // keep the leading zeros of the ids
+            column_types: vec![("id".to_string(), "VARCHAR".to_string())],
+            ..CsvOptions::default()
+        };
+        let mut session = FileSession::open(
+            Filename(test_file_path.into()),
+            &csv_options,
+            &DuckDbSettings::default(),
+        )?;
+        let result = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            SortSpec::default(),
+        )?;
+
+        let titles: Vec<&str> = result
+            .column_names
+            .iter()
+            .map(|column| column.title.as_str())
+            .collect();
+        assert_eq!(
+            titles,
+            vec!["id\n(Utf8)", "name\n(Utf8)", "booked\n(Date32)"]
+        );
+        assert_eq!(result.row_count, 3);
+        assert_eq!(result.rows[0][0].text, "001");
+        assert_eq!(result.rows[0][2].text, "2024-01-31");
+        assert_eq!(result.rows[1][1].text, "NULL");
+        assert_eq!(result.rows[2][2].text, "NULL");
+
+        fs::remove_file(test_file_path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sniff_csv() -> Result<(), Error> {
+        let test_file_path = "target/test_sniff.csv";
+        fs::write(
+            test_file_path,
+            "id;name;booked\n1;Coffee;2024-01-31\n2;Books;2024-02-01\n",
+        )?;
+
+        let session = FileSession::open(
+            Filename(test_file_path.into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default(),
+        )?;
+        let dialect = session.sniff_csv()?;
+        assert_eq!(dialect.delimiter, ";");
+        assert!(dialect.has_header);
+        assert_eq!(
+            dialect.columns,
+            vec![
+                ("id".to_string(), "BIGINT".to_string()),
+                ("name".to_string(), "VARCHAR".to_string()),
+                ("booked".to_string(), "DATE".to_string()),
+            ]
+        );
+        assert!(dialect
+            .read_csv
+            .contains("read_csv('target/test_sniff.csv'"));
+
+        // only csv files are sniffed
+        let session = FileSession::open(
+            Filename("test_data/AAPL_minute.parquet".into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default(),
+        )?;
+        assert!(session.sniff_csv().is_err());
+
+        fs::remove_file(test_file_path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_data_query() -> Result<(), Error> {
+        let mut session = FileSession::open(
+            Filename("test_data/AAPL_minute.parquet".into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default(),
+        )?;
+        let file_page = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            SortSpec::default(),
+        )?;
+
+        // the query result is paged and counted like a file
+        session.set_query(Some("SELECT * FROM data LIMIT 25;"))?;
+        let page = fetch_data(
+            &mut session,
+            PageNumber(3),
+            PageSize(10),
+            SortSpec::default(),
+        )?;
+        assert_eq!(page.row_count, 25);
+        assert_eq!(page.rows.len(), 5);
+        assert!(!page.row_count_cached);
+
+        session.set_query(Some("SELECT count(*) AS row_count FROM data"))?;
+        let page = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            SortSpec::default(),
+        )?;
+        assert_eq!(page.column_names[0].title.as_str(), "row_count\n(Int64)");
+        assert_eq!(page.rows[0][0].text, file_page.row_count.to_string());
+
+        // only a single query is accepted
+        assert!(session.set_query(Some("")).is_err());
+        assert!(session.set_query(Some("DROP VIEW data")).is_err());
+        assert!(session
+            .set_query(Some("CREATE TABLE copy AS SELECT 1"))
+            .is_err());
+        assert!(session.set_query(Some("SELECT 1; SELECT 2")).is_err());
+
+        // clearing the query goes back to the file
+        session.set_query(None)?;
+        let page = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            SortSpec::default(),
+        )?;
+        assert_eq!(page.row_count, file_page.row_count);
+        assert_eq!(page.column_names.len(), file_page.column_names.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_query() -> Result<(), Error> {
+        let mut session = FileSession::open(
+            Filename("test_data/AAPL_minute.parquet".into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default(),
+        )?;
+        session.set_filters(vec![ColumnFilter {
+            column: 0,
+            operator: FilterOperator::IsNotNull,
+            value: String::new(),
+            value2: String::new(),
+        }]);
+        session.set_search("AAPL".to_string());
+        let file_page = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            SortSpec::default(),
+        )?;
+
+        // a query that can't be loaded leaves the file as it was shown
+        let previous = session.set_query(Some("SELECT 1 AS one"))?;
+        assert!(session.filters().is_empty());
+        session.restore_query(previous);
+        assert_eq!(session.filters().len(), 1);
+        assert_eq!(session.search(), "AAPL");
+        let page = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            SortSpec::default(),
+        )?;
+        assert_eq!(page.row_count, file_page.row_count);
+        assert_eq!(page.schema, file_page.schema);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_data_filters() -> Result<(), Error> {
+        let mut session = FileSession::open(
+            Filename("test_data/AAPL_minute.csv".into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default(),
+        )?;
+        fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            SortSpec::default(),
+        )?;
+
+        // Volume between 100000 and 200000, and an earlier Timestamp
+        session.set_filters(vec![
+            ColumnFilter {
+                column: 5,
+                operator: FilterOperator::Between,
+                value: "100000".to_string(),
+                value2: "200000".to_string(),
+            },
+            ColumnFilter {
+                column: 0,
+                operator: FilterOperator::Before,
+                value: "2023-10-10".to_string(),
+                value2: String::new(),
+            },
+        ]);
+        let page = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            sort_on(5, SortDirection::Descending),
+        )?;
+        // the row count is recounted with the filters
+        assert!(!page.row_count_cached);
+        assert!(page.row_count > 0 && page.row_count < 13785);
+
+        // the filtered rows are sorted on Volume, descending
+        let volumes: Vec<i64> = page
+            .rows
+            .iter()
+            .map(|row| row[5].text.parse().unwrap())
+            .collect();
+        assert!(volumes.iter().all(|v| (100000..=200000).contains(v)));
+        assert!(volumes.windows(2).all(|w| w[0] >= w[1]));
+
+        // a value that is not a number is an error, not an empty page
+        session.set_filters(vec![ColumnFilter {
+            column: 5,
+            operator: FilterOperator::Equals,
+            value: "many".to_string(),
+            value2: String::new(),
+        }]);
+        assert!(fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            SortSpec::default(),
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_data_filters_keep_precision() -> Result<(), Error> {
+        let mut session = FileSession::open(
+            Filename("test_data/products.json".into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default(),
+        )?;
+
+        // the two values of each column are the same DOUBLE
+        session.set_query(Some(
+            "SELECT * FROM (VALUES (9007199254740992::BIGINT, 0.10000000000000001::DECIMAL(18,17)), \
+             (9007199254740993::BIGINT, 0.1::DECIMAL(18,17))) AS t(id, amount)",
+        ))?;
+        assert_eq!(session.schema()[0].sql_type, "BIGINT");
+        assert_eq!(session.schema()[1].sql_type, "DECIMAL(18,17)");
+
+        // both filters select the second row only
+        for (column, value) in [(0, "9007199254740993"), (1, "0.1")] {
+            session.set_filters(vec![ColumnFilter {
+                column,
+                operator: FilterOperator::Equals,
+                value: value.to_string(),
+                value2: String::new(),
+            }]);
+            let page = fetch_data(
+                &mut session,
+                PageNumber(1),
+                PageSize(10),
+                SortSpec::default(),
+            )?;
+            assert_eq!(page.row_count, 1, "{}", value);
+            assert_eq!(page.rows[0][0].text.as_str(), "9007199254740993");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_data_search() -> Result<(), Error> {
+        let mut session = FileSession::open(
+            Filename("test_data/products.json".into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default(),
+        )?;
+
+        // text is matched case-insensitively and the match is marked in the cell
+        session.set_search("electronics".to_string());
+        let page = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            SortSpec::default(),
+        )?;
+        assert_eq!(page.row_count, 2);
+        assert_eq!(page.rows.len(), 2);
+        assert_eq!(page.rows[0][2].text.as_str(), "«Electronics»");
+        assert_eq!(page.rows[0][1].text.as_str(), "Product A");
+
+        // numbers are searched as they are shown
+        session.set_search("99.99".to_string());
+        let page = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            SortSpec::default(),
+        )?;
+        assert_eq!(page.row_count, 1);
+        assert_eq!(page.rows[0][3].text.as_str(), "«99.99»");
+
+        // an empty search shows every row again
+        session.set_search(String::new());
+        let page = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            SortSpec::default(),
+        )?;
+        assert_eq!(page.row_count, 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_data_search_timestamps() -> Result<(), Error> {
+        let mut session = FileSession::open(
+            Filename("test_data/AAPL_minute.csv".into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default(),
+        )?;
+        assert_eq!(session.schema()[0].kind, ColumnKind::Timestamp);
+
+        // timestamps are searched in DuckDB's text form, not as the table shows them
+        session.set_search("2023-10-09 10:39".to_string());
+        let page = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            SortSpec::default(),
+        )?;
+        assert_eq!(page.row_count, 1);
+        // the shown text doesn't contain the search, so nothing is marked
+        assert!(page.rows[0][0].text.starts_with("2023-10-09T10:39:00"));
+        assert!(!page.rows[0][0].text.contains('«'));
+
+        session.set_search("2023-10-09T10:39".to_string());
+        let page = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            SortSpec::default(),
+        )?;
+        assert_eq!(page.row_count, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_data_multi_column_sort() -> Result<(), Error> {
+        let mut session = FileSession::open(
+            Filename("test_data/products.json".into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default(),
+        )?;
+
+        // category ascending, then price descending within a category
+        let sort = sort_on(2, SortDirection::Ascending).with_key(
+            SortKey {
+                column: 3,
+                direction: SortDirection::Descending,
+            },
+            true,
+        );
+        let page = fetch_data(&mut session, PageNumber(1), PageSize(10), sort)?;
+        let sorted: Vec<(&str, &str)> = page
+            .rows
+            .iter()
+            .map(|row| (row[2].text.as_str(), row[3].text.as_str()))
+            .collect();
+        assert_eq!(
+            sorted,
+            vec![
+                ("Books", "14.99"),
+                ("Clothing", "29.99"),
+                ("Electronics", "99.99"),
+                ("Electronics", "19.99"),
+                ("Food", "9.99"),
+            ]
+        );
+
+        // the headers show the direction and priority of the sorted columns
+        let headers = &page.column_names;
+        assert_eq!(headers[2].title.as_str(), "category #1\n(Utf8)");
+        assert_eq!(headers[2].sort_order, slint::SortOrder::Ascending);
+        assert_eq!(headers[3].title.as_str(), "price #2\n(Float64)");
+        assert_eq!(headers[3].sort_order, slint::SortOrder::Descending);
+        assert_eq!(headers[1].title.as_str(), "name\n(Utf8)");
+        assert_eq!(headers[1].sort_order, slint::SortOrder::Unsorted);
+
+        // a single sorted column has no priority to show
+        let page = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            sort_on(3, SortDirection::Ascending),
+        )?;
+        assert_eq!(page.column_names[3].title.as_str(), "price\n(Float64)");
+        assert_eq!(page.rows[0][3].text.as_str(), "9.99");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_data_sorted_pages_are_stable() -> Result<(), Error> {
+        let mut session = FileSession::open(
+            Filename("test_data/AAPL_minute.parquet".into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default(),
+        )?;
+        let symbol = session
+            .schema()
+            .iter()
+            .position(|column| column.name == "Symbol")
+            .expect("Expected a Symbol column");
+        let timestamp = session
+            .schema()
+            .iter()
+            .position(|column| column.name == "Timestamp")
+            .expect("Expected a Timestamp column");
+
+        let unsorted = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            SortSpec::default(),
+        )?;
+
+        // every row has the same symbol, so the order comes from the tie-breaker
+        for direction in [SortDirection::Ascending, SortDirection::Descending] {
+            // the tied rows keep the order of the file
+            let first = fetch_data(
+                &mut session,
+                PageNumber(1),
+                PageSize(10),
+                sort_on(symbol, direction),
+            )?;
+            assert_eq!(first.rows, unsorted.rows);
+
+            let mut seen = HashSet::new();
+            let mut page_number = 1;
+            let row_count = loop {
+                let page = fetch_data(
+                    &mut session,
+                    PageNumber(page_number),
+                    PageSize(5000),
+                    sort_on(symbol, direction),
+                )?;
+                if page.rows.is_empty() {
+                    break page.row_count;
+                }
+                for row in &page.rows {
+                    // the timestamps are unique, a repeated one is a duplicated row
+                    assert!(seen.insert(row[timestamp].text.to_string()));
+                }
+                page_number += 1;
+            };
+            assert_eq!(seen.len(), row_count as usize);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_data_sorts_by_column_name() -> Result<(), Error> {
+        let mut session = FileSession::open(
+            Filename("test_data/products.json".into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default(),
+        )?;
+
+        // the query moves price to the first column, the sort follows it by name
+        session.set_query(Some("SELECT price, name FROM data"))?;
+        let page = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            sort_on(0, SortDirection::Descending),
+        )?;
+        assert_eq!(page.rows[0][0].text.as_str(), "99.99");
+        assert_eq!(page.rows[0][1].text.as_str(), "Product E");
+
+        // a sort on a column that doesn't exist is an error
+        assert!(fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            sort_on(2, SortDirection::Ascending),
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_order_by_clause() -> Result<(), Error> {
+        let schema = vec![
+            ColumnSchema {
+                name: "id".to_string(),
+                kind: ColumnKind::Number,
+                sql_type: "BIGINT".to_string(),
+            },
+            ColumnSchema {
+                name: "two words".to_string(),
+                kind: ColumnKind::Text,
+                sql_type: "VARCHAR".to_string(),
+            },
+            ColumnSchema {
+                name: "day".to_string(),
+                kind: ColumnKind::Date,
+                sql_type: "DATE".to_string(),
+            },
+        ];
+        assert_eq!(order_by_clause(&schema, &SortSpec::default(), None)?, "");
+        assert_eq!(
+            order_by_clause(&schema, &sort_on(1, SortDirection::Descending), None)?,
+            " ORDER BY \"two words\" DESC, \"id\" ASC, \"day\" ASC"
+        );
+        // the row numbers are the only tie-breakers when the source has them
+        assert_eq!(
+            order_by_clause(
+                &schema,
+                &sort_on(1, SortDirection::Descending),
+                Some(&["filename", "file_row_number"][..])
+            )?,
+            " ORDER BY \"two words\" DESC, \"filename\" ASC, \"file_row_number\" ASC"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_data_quoted_file_names() -> Result<(), Error> {
+        // names that end a string literal or need more than one byte per character
+        let test_files = [
+            ("test_data/AAPL_minute.csv", "target/O'Brien_export.csv"),
+            ("test_data/products.json", "target/it's 東京 Zürich.json"),
+            (
+                "test_data/AAPL_minute.parquet",
+                "target/x'); SELECT 1; --.parquet",
+            ),
+        ];
+
+        for (original, copy) in test_files {
+            fs::copy(original, copy)?;
+            let mut session = FileSession::open(
+                Filename(copy.into()),
+                &CsvOptions::default(),
+                &DuckDbSettings::default(),
+            )?;
+            let result = fetch_data(
+                &mut session,
+                PageNumber(1),
+                PageSize(10),
+                SortSpec::default(),
+            )?;
+            assert!(result.row_count > 0, "Expected rows in '{}'", copy);
+            fs::remove_file(copy)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_data_bracketed_file_names() -> Result<(), Error> {
+        // `sales[1].parquet` read as a glob matches `sales1.parquet`, which is empty
+        fs::copy("test_data/empty.parquet", "target/sales1.parquet")?;
+        let test_files = [
+            ("test_data/AAPL_minute.csv", "target/report [2024].csv"),
+            ("test_data/AAPL_minute.parquet", "target/sales[1].parquet"),
+        ];
+
+        for (original, copy) in test_files {
+            fs::copy(original, copy)?;
+            let mut expected = FileSession::open(
+                Filename(original.into()),
+                &CsvOptions::default(),
+                &DuckDbSettings::default(),
+            )?;
+            let expected = fetch_data(
+                &mut expected,
+                PageNumber(1),
+                PageSize(10),
+                SortSpec::default(),
+            )?;
+
+            let mut session = FileSession::open(
+                Filename(copy.into()),
+                &CsvOptions::default(),
+                &DuckDbSettings::default(),
+            )?;
+            let result = fetch_data(
+                &mut session,
+                PageNumber(1),
+                PageSize(10),
+                SortSpec::default(),
+            )?;
+            assert_eq!(result.row_count, expected.row_count, "Rows of '{}'", copy);
+            assert_eq!(result.schema, expected.schema);
+            fs::remove_file(copy)?;
+        }
+        fs::remove_file("target/sales1.parquet")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_session_cannot_write_files() -> Result<(), Error> {
+        let target = "target/sandbox_copy.csv";
+        let _ = fs::remove_file(target);
+        let mut session = FileSession::open(
+            Filename("test_data/AAPL_minute.csv".into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default(),
+        )?;
+
+        // the sql editor
+        let queries = [
+            "COPY data TO 'target/sandbox_copy.csv'",
+            "COPY (SELECT * FROM data) TO 'target/sandbox_copy.csv' (FORMAT CSV)",
+            "SELECT 1) AS q; COPY data TO 'target/sandbox_copy.csv'; SELECT * FROM (SELECT 1",
+            "EXPORT DATABASE 'target/sandbox_export'",
+            "ATTACH 'target/sandbox.duckdb' AS copy",
+            "INSTALL httpfs",
+            "SET enable_external_access = true",
+            // only the opened file can be read
+            "SELECT * FROM read_json_auto('test_data/products.json')",
+        ];
+        for sql in queries {
+            assert!(
+                session.set_query(Some(sql)).is_err(),
+                "Expected '{}' to fail",
+                sql
+            );
+        }
+
+        // the filter bar and the search box pass their values as parameters
+        let value = "x'); COPY data TO 'target/sandbox_copy.csv'; --";
+        session.set_filters(vec![ColumnFilter {
+            column: 6,
+            operator: FilterOperator::Contains,
+            value: value.to_string(),
+            value2: String::new(),
+        }]);
+        session.set_search(value.to_string());
+        let page = fetch_data(
+            &mut session,
+            PageNumber(1),
+            PageSize(10),
+            SortSpec::default(),
+        )?;
+        assert_eq!(page.row_count, 0);
+
+        // and the connection itself refuses to write or to be unlocked
+        let conn = session.conn();
+        assert!(conn
+            .execute_batch("COPY (SELECT 1) TO 'target/sandbox_copy.csv'")
+            .is_err());
+        assert!(conn
+            .execute_batch("SET enable_external_access = true")
+            .is_err());
+        assert!(conn
+            .execute_batch("SET lock_configuration = false")
+            .is_err());
+
+        // the csv import options are written as string literals
+        let csv_options = CsvOptions {
+            null_string: value.to_string(),
+            ..CsvOptions::default()
+        };
+        let _ = FileSession::open(
+            Filename("test_data/AAPL_minute.csv".into()),
+            &csv_options,
+            &DuckDbSettings::default(),
+        );
+
+        assert!(!Path::new(target).exists());
+        assert!(!Path::new("target/sandbox_export").exists());
+        assert!(!Path::new("target/sandbox.duckdb").exists());
+
+        Ok(())
+    }
 }
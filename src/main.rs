@@ -6,17 +6,36 @@ use anyhow::Error;
 use clap::Parser;
 
 use native_dialog::{FileDialog, MessageDialog, MessageType};
-use slint::SharedString;
-use std::path::Path;
+use slint::{Model, ModelRc, SharedString, VecModel};
+use std::path::PathBuf;
 use std::process;
 
+mod csv_options;
+mod error;
+mod filter;
 mod loader;
 mod model;
+mod parquet_metadata;
+mod request;
+mod session;
+mod settings;
+mod sql;
 mod utils;
+mod worker;
 
-use loader::{set_ui_defaults, update_table_async};
-use model::{Filename, PageNumber, PageSize, SortIndex, SortOrder};
-use utils::file_exists;
+use csv_options::CsvOptions;
+use filter::{ColumnFilter, FilterOperator};
+use loader::{
+    csv_options_from_ui, filter_table_async, inspect_parquet_async, open_file_async,
+    run_query_async, search_table_async, select_sheet_async, select_table_async,
+    set_csv_options_ui, set_settings_ui, set_sort_ui, set_ui_defaults, settings_from_ui,
+    show_error, show_settings_file_error, sniff_csv_async, sort_from_ui, update_table_async,
+};
+use model::{Filename, PageNumber, PageSize, SortDirection, SortKey};
+use request::RequestTracker;
+use settings::{default_config_path, DuckDbSettings};
+use utils::{file_exists, is_csv_file, is_dataset_path};
+use worker::Loader;
 
 #[derive(Parser)]
 #[command(
@@ -26,8 +45,34 @@ use utils::file_exists;
     about = "Slint DuckDB File Viewer"
 )]
 struct CLIArgs {
-    #[arg(short, long, help = "File to open", required = false)]
+    #[arg(
+        short,
+        long,
+        help = "File, directory or glob of parquet files to open",
+        required = false
+    )]
     filename: Option<String>,
+
+    #[arg(
+        long,
+        help = "Memory DuckDB may use, e.g. 4GB or 50%, overrides the settings file"
+    )]
+    memory_limit: Option<String>,
+
+    #[arg(long, help = "Threads DuckDB may use, overrides the settings file")]
+    threads: Option<u32>,
+
+    #[arg(
+        long,
+        help = "Directory DuckDB spills to when out of memory, overrides the settings file"
+    )]
+    temp_directory: Option<String>,
+
+    #[arg(
+        long,
+        help = "Settings file to use instead of the one in the config directory"
+    )]
+    config: Option<PathBuf>,
 }
 
 // Include the UI components from the Slint file
@@ -40,6 +85,36 @@ async fn main() -> Result<(), Error> {
     let ui = AppWindow::new()?;
     let ui_handle1 = ui.as_weak();
 
+    // the settings file, with the command line taking precedence over it
+    let config_path = args.config.clone().or_else(default_config_path);
+    let mut settings_error = None;
+    let mut settings = match &config_path {
+        Some(path) => DuckDbSettings::load(path).unwrap_or_else(|e| {
+            eprintln!("{:#}", e);
+            settings_error = Some(e);
+            DuckDbSettings::default()
+        }),
+        None => DuckDbSettings::default(),
+    };
+    if let Some(memory_limit) = &args.memory_limit {
+        settings.memory_limit = memory_limit.clone();
+    }
+    if let Some(threads) = args.threads {
+        settings.threads = threads;
+    }
+    if let Some(temp_directory) = &args.temp_directory {
+        settings.temp_directory = temp_directory.clone();
+    }
+    set_settings_ui(&ui, &settings);
+    if let Some(e) = &settings_error {
+        show_settings_file_error(&ui, e);
+    }
+
+    // owns the currently opened file and runs every query on its own thread
+    let loader = Loader::spawn(settings)?;
+    // only the most recent table request is applied to the ui
+    let request_tracker = RequestTracker::new();
+
     // Set initial page to home
     ui.set_current_page(SharedString::from("home"));
 
@@ -55,48 +130,282 @@ async fn main() -> Result<(), Error> {
     });
 
     ui.on_show_error_dialog({
-        move |error_message| {
+        let ui_handle = ui.as_weak();
+        move |error_title, error_message| {
             let _ = MessageDialog::new()
-                .set_title("Error Reading File")
-                .set_text(&format!("{}", error_message))
+                .set_title(&error_title)
+                .set_text(&error_message)
                 .set_type(MessageType::Error)
                 .show_alert()
                 .map_err(|e| println!("Failed to show error dialog: {}", e));
+            // the alert blocks until it is closed, the next error shows a new one
+            if let Some(ui) = ui_handle.upgrade() {
+                ui.global::<GlobalState>().set_has_error(false);
+            }
         }
     });
 
     ui.global::<GlobalState>().on_update_table_async({
         let ui_handle = ui.as_weak();
+        let loader = loader.clone();
+        let request_tracker = request_tracker.clone();
         move || {
             let ui = ui_handle.unwrap();
+            let ticket = request_tracker.begin();
             // get the data from the ui to send to the loader
-            let filename = ui.global::<GlobalState>().get_filename();
             let page_number = ui.global::<GlobalState>().get_page_number();
             let page_size = ui.global::<GlobalState>().get_page_size();
-            let sort_index = ui.global::<GlobalState>().get_sort_index();
-            let sort_order = ui.global::<GlobalState>().get_sort_order();
+            let sort = sort_from_ui(&ui);
             let value = ui_handle.clone();
+            let loader = loader.clone();
             tokio::spawn(async move {
                 match update_table_async(
                     &value,
-                    false,
-                    Filename(filename),
+                    &loader,
+                    ticket,
                     PageNumber(page_number),
                     PageSize(page_size),
-                    SortIndex(sort_index),
-                    SortOrder(sort_order),
-                ) {
+                    sort,
+                )
+                .await
+                {
                     Ok(_) => {}
-                    Err(_e) => {
-                        // TODO: show error dialog
-                    }
+                    Err(e) => show_error(&value, e),
+                }
+            });
+        }
+    });
+
+    ui.global::<GlobalState>().on_sort_column({
+        let ui_handle = ui.as_weak();
+        move |column, descending, add| {
+            let ui = ui_handle.unwrap();
+            let key = SortKey {
+                column: column.max(0) as usize,
+                direction: if descending {
+                    SortDirection::Descending
+                } else {
+                    SortDirection::Ascending
+                },
+            };
+            let sort = sort_from_ui(&ui).with_key(key, add);
+            set_sort_ui(&ui, &sort);
+        }
+    });
+
+    ui.global::<GlobalState>().on_select_table_async({
+        let ui_handle = ui.as_weak();
+        let loader = loader.clone();
+        let request_tracker = request_tracker.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            let ticket = request_tracker.begin();
+            let table_index = ui.global::<GlobalState>().get_table_index();
+            let page_size = ui.global::<GlobalState>().get_page_size();
+            let value = ui_handle.clone();
+            let loader = loader.clone();
+            tokio::spawn(async move {
+                match select_table_async(
+                    &value,
+                    &loader,
+                    ticket,
+                    table_index as usize,
+                    PageSize(page_size),
+                )
+                .await
+                {
+                    Ok(_) => {}
+                    Err(e) => show_error(&value, e),
                 }
             });
         }
     });
 
+    ui.global::<GlobalState>().on_select_sheet_async({
+        let ui_handle = ui.as_weak();
+        let loader = loader.clone();
+        let request_tracker = request_tracker.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            let ticket = request_tracker.begin();
+            let sheet_index = ui.global::<GlobalState>().get_table_index();
+            let header_row = ui.global::<GlobalState>().get_header_row();
+            let page_size = ui.global::<GlobalState>().get_page_size();
+            let value = ui_handle.clone();
+            let loader = loader.clone();
+            tokio::spawn(async move {
+                match select_sheet_async(
+                    &value,
+                    &loader,
+                    ticket,
+                    sheet_index as usize,
+                    header_row.max(0) as u32,
+                    PageSize(page_size),
+                )
+                .await
+                {
+                    Ok(_) => {}
+                    Err(e) => show_error(&value, e),
+                }
+            });
+        }
+    });
+
+    ui.global::<GlobalState>().on_add_filter({
+        let ui_handle = ui.as_weak();
+        let loader = loader.clone();
+        let request_tracker = request_tracker.clone();
+        move |column, operator, value, value2| {
+            let ui = ui_handle.unwrap();
+            let column_name = ui
+                .global::<GlobalState>()
+                .get_filter_columns()
+                .row_data(column.max(0) as usize)
+                .unwrap_or_default();
+            let label = filter_label(&column_name, &operator, &value, &value2);
+            let mut items: Vec<FilterItem> =
+                ui.global::<GlobalState>().get_filters().iter().collect();
+            items.push(FilterItem {
+                column,
+                operator,
+                value,
+                value2,
+                label: label.into(),
+            });
+            apply_filters(&ui, &loader, &request_tracker, items);
+        }
+    });
+
+    ui.global::<GlobalState>().on_remove_filter({
+        let ui_handle = ui.as_weak();
+        let loader = loader.clone();
+        let request_tracker = request_tracker.clone();
+        move |index| {
+            let ui = ui_handle.unwrap();
+            let mut items: Vec<FilterItem> =
+                ui.global::<GlobalState>().get_filters().iter().collect();
+            if index >= 0 && (index as usize) < items.len() {
+                items.remove(index as usize);
+            }
+            apply_filters(&ui, &loader, &request_tracker, items);
+        }
+    });
+
+    ui.global::<GlobalState>().on_clear_filters({
+        let ui_handle = ui.as_weak();
+        let loader = loader.clone();
+        let request_tracker = request_tracker.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            apply_filters(&ui, &loader, &request_tracker, Vec::new());
+        }
+    });
+
+    ui.global::<GlobalState>().on_search_async({
+        let ui_handle = ui.as_weak();
+        let loader = loader.clone();
+        let request_tracker = request_tracker.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            let ticket = request_tracker.begin();
+            let search = ui
+                .global::<GlobalState>()
+                .get_search_text()
+                .trim()
+                .to_string();
+            let page_size = ui.global::<GlobalState>().get_page_size();
+            let sort = sort_from_ui(&ui);
+            let value = ui_handle.clone();
+            let loader = loader.clone();
+            tokio::spawn(async move {
+                match search_table_async(&value, &loader, ticket, search, PageSize(page_size), sort)
+                    .await
+                {
+                    Ok(_) => {}
+                    Err(e) => show_error(&value, e),
+                }
+            });
+        }
+    });
+
+    ui.global::<GlobalState>().on_run_query_async({
+        let ui_handle = ui.as_weak();
+        let loader = loader.clone();
+        let request_tracker = request_tracker.clone();
+        move |clear| {
+            let ui = ui_handle.unwrap();
+            let ticket = request_tracker.begin();
+            ui.global::<GlobalState>()
+                .set_sql_error(SharedString::from(""));
+            // the query reads the `data` view of the opened file
+            let sql = if clear {
+                None
+            } else {
+                Some(ui.global::<GlobalState>().get_sql_text().to_string())
+            };
+            let page_size = ui.global::<GlobalState>().get_page_size();
+            let value = ui_handle.clone();
+            let loader = loader.clone();
+            tokio::spawn(async move {
+                let query_active = sql.is_some();
+                let result =
+                    run_query_async(&value, &loader, ticket, sql, PageSize(page_size)).await;
+                let _ = value.upgrade_in_event_loop(move |handle| match result {
+                    Ok(true) => handle
+                        .global::<GlobalState>()
+                        .set_query_active(query_active),
+                    Ok(false) => {}
+                    Err(e) => handle
+                        .global::<GlobalState>()
+                        .set_sql_error(SharedString::from(e.to_string())),
+                });
+            });
+        }
+    });
+
+    ui.global::<GlobalState>().on_sniff_csv_async({
+        let ui_handle = ui.as_weak();
+        let loader = loader.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            ui.global::<GlobalState>().set_file_info_loading(true);
+            ui.global::<GlobalState>()
+                .set_file_info_error(SharedString::from(""));
+            let value = ui_handle.clone();
+            let loader = loader.clone();
+            tokio::spawn(async move {
+                sniff_csv_async(&value, &loader).await;
+            });
+        }
+    });
+
+    ui.global::<GlobalState>().on_inspect_parquet_async({
+        let ui_handle = ui.as_weak();
+        let loader = loader.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            ui.global::<GlobalState>().set_inspect_loading(true);
+            ui.global::<GlobalState>()
+                .set_inspect_error(SharedString::from(""));
+            let value = ui_handle.clone();
+            let loader = loader.clone();
+            tokio::spawn(async move {
+                inspect_parquet_async(&value, &loader).await;
+            });
+        }
+    });
+
+    ui.global::<GlobalState>().on_cancel_query({
+        let loader = loader.clone();
+        move || {
+            loader.cancel();
+        }
+    });
+
     ui.global::<GlobalState>().on_open_file_async({
         let ui_handle = ui.as_weak();
+        let loader = loader.clone();
+        let request_tracker = request_tracker.clone();
         move || {
             let ui = ui_handle.unwrap();
 
@@ -107,7 +416,12 @@ async fn main() -> Result<(), Error> {
             // open the file dialog for the user to select a file
             let result = FileDialog::new()
                 .add_filter("Parquet files", &["parquet"])
-                .add_filter("CSV", &["csv"])
+                .add_filter("CSV", &["csv", "tsv", "gz", "zst"])
+                .add_filter("JSON", &["json", "jsonl", "ndjson", "gz", "zst"])
+                .add_filter("Arrow IPC / Feather", &["arrow", "feather", "ipc"])
+                .add_filter("DuckDB database", &["duckdb", "db"])
+                .add_filter("SQLite database", &["sqlite", "sqlite3", "db"])
+                .add_filter("Excel workbook", &["xlsx"])
                 .show_open_single_file();
 
             // get the filename
@@ -128,63 +442,110 @@ async fn main() -> Result<(), Error> {
                 }
             };
 
-            // the user has selected an existing file.
-            // set the default values on the ui. This will clear out any previous data
-            set_ui_defaults(&ui);
+            // the user has selected an existing file. A new file starts with the
+            // sniffed csv options
+            open_path(
+                &ui,
+                &loader,
+                &request_tracker,
+                filename,
+                CsvOptions::default(),
+            );
+        }
+    });
 
-            // call the update ui async function
-            let value = ui_handle.clone();
-            tokio::spawn(async move {
-                match update_table_async(
-                    &value,
-                    true,
-                    Filename(SharedString::from(filename.as_str())),
-                    PageNumber(1),
-                    PageSize(20),
-                    SortIndex(-1),
-                    SortOrder(0),
-                ) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        // set the error message on the ui and this should make the error dialog show
-                        let _ = value.upgrade_in_event_loop(move |handle| {
-                            handle
-                                .global::<GlobalState>()
-                                .set_error_message(SharedString::from(e.to_string()));
-                            handle.global::<GlobalState>().set_has_error(true);
-                        });
-                    }
+    ui.global::<GlobalState>().on_open_folder_async({
+        let ui_handle = ui.as_weak();
+        let loader = loader.clone();
+        let request_tracker = request_tracker.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            ui.set_current_page(SharedString::from("home"));
+
+            // the parquet files in the folder and its subfolders are opened as one dataset
+            let dirname: String = match FileDialog::new().show_open_single_dir() {
+                Ok(Some(path)) => path.display().to_string(),
+                // ignore
+                Ok(None) => return,
+                Err(_e) => {
+                    eprintln!("Failed to open folder dialog");
+                    return;
                 }
-            });
+            };
+
+            open_path(
+                &ui,
+                &loader,
+                &request_tracker,
+                dirname,
+                CsvOptions::default(),
+            );
+        }
+    });
+
+    ui.global::<GlobalState>().on_apply_csv_options({
+        let ui_handle = ui.as_weak();
+        let loader = loader.clone();
+        let request_tracker = request_tracker.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            let csv_options = match csv_options_from_ui(&ui) {
+                Ok(csv_options) => csv_options,
+                Err(e) => {
+                    // keep the options page open so the overrides can be fixed
+                    ui.global::<GlobalState>()
+                        .set_csv_error(SharedString::from(e.to_string()));
+                    return;
+                }
+            };
+            ui.set_current_page(SharedString::from("home"));
+
+            // reopen the csv file with the chosen options
+            let filename = ui.global::<GlobalState>().get_csv_filename().to_string();
+            open_path(&ui, &loader, &request_tracker, filename, csv_options);
+        }
+    });
+
+    ui.global::<GlobalState>().on_save_settings({
+        let ui_handle = ui.as_weak();
+        let loader = loader.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            let state = ui.global::<GlobalState>();
+            // check the values with DuckDB before saving them
+            let settings = match settings_from_ui(&ui)
+                .and_then(|settings| settings.effective().map(|_| settings))
+            {
+                Ok(settings) => settings,
+                Err(e) => {
+                    state.set_settings_error(SharedString::from(format!("{:#}", e)));
+                    return;
+                }
+            };
+            if let Some(path) = &config_path {
+                if let Err(e) = settings.save(path) {
+                    state.set_settings_error(SharedString::from(format!("{:#}", e)));
+                    return;
+                }
+            }
+            set_settings_ui(&ui, &settings);
+            loader.set_settings(settings);
         }
     });
 
     // check that a file was provided and that it exists
-    // if no file was provided or it is not a file, then do nothing
+    // if no file was provided or it is not a file, directory or glob, then do nothing
     if let Some(filename) = args.filename {
-        if file_exists(&filename) {
+        if file_exists(&filename) || is_dataset_path(&filename) {
+            // load in the background so the window shows right away with the spinner
             let ui = ui_handle1.unwrap();
-            let path = Path::new(&filename);
-            set_ui_defaults(&ui);
-
-            // load the data from the file
-            let filename = Filename(SharedString::from(format!("{}", path.display())));
-            let value = ui_handle1.clone();
-            match update_table_async(
-                &value,
-                true,
+            open_path(
+                &ui,
+                &loader,
+                &request_tracker,
                 filename,
-                PageNumber(1),
-                PageSize(20),
-                SortIndex(-1),
-                SortOrder(0),
-            ) {
-                Ok(_) => {}
-                Err(e) => {
-                    // TODO: show error dialog
-                    println!("Error: {}", e)
-                }
-            }
+                CsvOptions::default(),
+            );
         } else {
             eprintln!("File '{}' does not exist.", filename);
         }
@@ -193,3 +554,108 @@ async fn main() -> Result<(), Error> {
     ui.run()?;
     Ok(())
 }
+
+// Resets the ui and opens `filename` on the loader thread, reading csv files with
+// `csv_options`. Errors are shown in the error dialog.
+fn open_path(
+    ui: &AppWindow,
+    loader: &Loader,
+    request_tracker: &RequestTracker,
+    filename: String,
+    csv_options: CsvOptions,
+) {
+    // set the default values on the ui. This will clear out any previous data
+    set_ui_defaults(ui);
+    // the options page shows the options the file is read with
+    set_csv_options_ui(ui, &csv_options);
+    // the import options stay in the menu when a csv file fails to load
+    if is_csv_file(&filename) {
+        ui.global::<GlobalState>().set_is_csv(true);
+        ui.global::<GlobalState>()
+            .set_csv_filename(SharedString::from(filename.as_str()));
+    }
+    // pages still loading for the previous file are discarded
+    let ticket = request_tracker.begin();
+    let page_size = ui.global::<GlobalState>().get_page_size();
+
+    // stop any query still running on the previous file so the loader is free
+    loader.cancel();
+
+    // call the update ui async function
+    let value = ui.as_weak();
+    let loader = loader.clone();
+    tokio::spawn(async move {
+        match open_file_async(
+            &value,
+            &loader,
+            ticket,
+            Filename(SharedString::from(filename.as_str())),
+            csv_options,
+            PageSize(page_size),
+        )
+        .await
+        {
+            Ok(_) => {}
+            Err(e) => show_error(&value, e),
+        }
+    });
+}
+
+// Reloads the first page of the rows `items` select, keeping the page size and sort,
+// and shows them in the filter bar once the page is shown.
+fn apply_filters(
+    ui: &AppWindow,
+    loader: &Loader,
+    request_tracker: &RequestTracker,
+    items: Vec<FilterItem>,
+) {
+    let filters: Vec<ColumnFilter> = items
+        .iter()
+        .filter_map(|item| {
+            Some(ColumnFilter {
+                column: usize::try_from(item.column).ok()?,
+                operator: FilterOperator::from_label(&item.operator)?,
+                value: item.value.trim().to_string(),
+                value2: item.value2.trim().to_string(),
+            })
+        })
+        .collect();
+
+    let ticket = request_tracker.begin();
+    ui.global::<GlobalState>().set_page_number(1);
+    ui.global::<GlobalState>().set_page_loading(true);
+    ui.global::<GlobalState>().set_pagination_enabled(false);
+    let page_size = ui.global::<GlobalState>().get_page_size();
+    let sort = sort_from_ui(ui);
+
+    let value = ui.as_weak();
+    let loader = loader.clone();
+    tokio::spawn(async move {
+        match filter_table_async(&value, &loader, ticket, filters, PageSize(page_size), sort).await
+        {
+            // the chips only change once the table shows their rows, a rejected filter
+            // would otherwise be sent again with every later change
+            Ok(true) => {
+                let _ = value.upgrade_in_event_loop(move |handle| {
+                    handle
+                        .global::<GlobalState>()
+                        .set_filters(ModelRc::new(VecModel::from(items)));
+                });
+            }
+            Ok(false) => {}
+            Err(e) => show_error(&value, e),
+        }
+    });
+}
+
+// The text of a filter shown in the filter bar, e.g. `price between 10 and 20`.
+fn filter_label(column: &str, operator: &str, value: &str, value2: &str) -> String {
+    let (value, value2) = (value.trim(), value2.trim());
+    match operator {
+        "is null" | "is not null" => format!("{} {}", column, operator),
+        "between" if value2.is_empty() => format!("{} >= {}", column, value),
+        "between" if value.is_empty() => format!("{} <= {}", column, value2),
+        "between" => format!("{} between {} and {}", column, value, value2),
+        _ => format!("{} {} {}", column, operator, value),
+    }
+}
@@ -5,8 +5,12 @@ use slint::TableColumn;
 #[derive(Debug)]
 pub struct QueryResult {
     pub column_names: Vec<TableColumn>,
+    // the name and kind of each column, used by the filter bar
+    pub schema: Vec<ColumnSchema>,
     pub rows: Vec<Vec<StandardListViewItem>>,
     pub row_count: i32,
+    // true if the row count came from the session cache instead of a count query
+    pub row_count_cached: bool,
     pub duration: std::time::Duration,
 }
 
@@ -14,5 +18,109 @@ pub struct QueryResult {
 pub struct Filename(pub SharedString);
 pub struct PageNumber(pub i32);
 pub struct PageSize(pub i32);
-pub struct SortIndex(pub i32);
-pub struct SortOrder(pub i32);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+/// A column the table is sorted on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SortKey {
+    /// The 0-indexed column
+    pub column: usize,
+    pub direction: SortDirection,
+}
+
+/// The columns the table is sorted on, the first one has the highest priority.
+/// Empty to show the rows in the order of the file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SortSpec(pub Vec<SortKey>);
+
+impl SortSpec {
+    /// The sort after clicking the header of `key.column`. A plain click sorts on the
+    /// column alone, a shift-click (`add`) appends it to the sort or changes its
+    /// direction while keeping its priority.
+    pub fn with_key(&self, key: SortKey, add: bool) -> SortSpec {
+        if !add {
+            return SortSpec(vec![key]);
+        }
+        let mut keys = self.0.clone();
+        match keys.iter_mut().find(|sorted| sorted.column == key.column) {
+            Some(sorted) => sorted.direction = key.direction,
+            None => keys.push(key),
+        }
+        SortSpec(keys)
+    }
+
+    /// The 1-based priority of `column` in the sort, if it is sorted on.
+    pub fn priority(&self, column: usize) -> Option<usize> {
+        self.0
+            .iter()
+            .position(|key| key.column == column)
+            .map(|index| index + 1)
+    }
+}
+
+/// The page and sort shown in the table, used to restore the table when a query is cancelled.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TableView {
+    pub page_number: i32,
+    pub page_size: i32,
+    pub sort: SortSpec,
+}
+
+/// The kind of values in a column, which decides the filters offered for it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColumnKind {
+    Text,
+    Number,
+    Date,
+    Timestamp,
+    Other,
+}
+
+/// A column as it is referenced in queries, e.g. by filters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColumnSchema {
+    pub name: String,
+    pub kind: ColumnKind,
+    /// The DuckDB type of the column, e.g. `DECIMAL(18,3)`, which filter values are cast to
+    pub sql_type: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(column: usize, direction: SortDirection) -> SortKey {
+        SortKey { column, direction }
+    }
+
+    #[test]
+    fn test_sort_spec_with_key() {
+        let sort = SortSpec::default().with_key(key(2, SortDirection::Ascending), false);
+        assert_eq!(sort, SortSpec(vec![key(2, SortDirection::Ascending)]));
+
+        // a shift-click appends the column to the sort
+        let sort = sort.with_key(key(0, SortDirection::Ascending), true);
+        assert_eq!(sort.priority(2), Some(1));
+        assert_eq!(sort.priority(0), Some(2));
+        assert_eq!(sort.priority(1), None);
+
+        // and changes the direction of a sorted column without moving it
+        let sort = sort.with_key(key(2, SortDirection::Descending), true);
+        assert_eq!(
+            sort,
+            SortSpec(vec![
+                key(2, SortDirection::Descending),
+                key(0, SortDirection::Ascending)
+            ])
+        );
+
+        // a plain click starts over
+        let sort = sort.with_key(key(0, SortDirection::Descending), false);
+        assert_eq!(sort, SortSpec(vec![key(0, SortDirection::Descending)]));
+    }
+}
@@ -0,0 +1,161 @@
+use anyhow::{Context, Error};
+use duckdb::Connection;
+
+/// How a parquet file or dataset was written, read with DuckDB's parquet metadata
+/// functions. Every value is converted to text for display and each row starts
+/// with the file it belongs to.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParquetMetadata {
+    /// Writer, row count, row group count and format version of each file
+    pub files: Vec<Vec<String>>,
+    /// The key/value metadata of each file, e.g. the pandas or arrow schema
+    pub key_values: Vec<Vec<String>>,
+    /// Each schema element with its physical, repetition, converted and logical type
+    pub schema: Vec<Vec<String>>,
+    /// Each column chunk of each row group with its sizes, codec, encodings and statistics
+    pub column_chunks: Vec<Vec<String>>,
+}
+
+// The columns shown for each metadata function, in the order of the inspect tables
+const FILE_COLUMNS: &[&str] = &[
+    "file_name",
+    "created_by",
+    "num_rows",
+    "num_row_groups",
+    "format_version",
+];
+const KEY_VALUE_COLUMNS: &[&str] = &["file_name", "key", "value"];
+const SCHEMA_COLUMNS: &[&str] = &[
+    "file_name",
+    "name",
+    "type",
+    "repetition_type",
+    "converted_type",
+    "logical_type",
+];
+const COLUMN_CHUNK_COLUMNS: &[&str] = &[
+    "file_name",
+    "row_group_id",
+    "row_group_num_rows",
+    "path_in_schema",
+    "type",
+    "compression",
+    "encodings",
+    "total_compressed_size",
+    "total_uncompressed_size",
+    "stats_min_value",
+    "stats_max_value",
+    "stats_null_count",
+];
+
+/// Reads the metadata of the parquet files matching `files`, a quoted file name or
+/// glob such as `'lake/**/*.parquet'`.
+///
+/// # Errors
+///
+/// Returns an error if any of the metadata queries fails, e.g. for a corrupt footer
+pub fn read_parquet_metadata(conn: &Connection, files: &str) -> Result<ParquetMetadata, Error> {
+    Ok(ParquetMetadata {
+        files: read_text_rows(
+            conn,
+            FILE_COLUMNS,
+            &format!("parquet_file_metadata({})", files),
+            Some("file_name"),
+        )?,
+        key_values: read_text_rows(
+            conn,
+            KEY_VALUE_COLUMNS,
+            &format!("parquet_kv_metadata({})", files),
+            Some("file_name, key"),
+        )?,
+        // sorting would lose the order of the schema elements, which nests them
+        schema: read_text_rows(
+            conn,
+            SCHEMA_COLUMNS,
+            &format!("parquet_schema({})", files),
+            None,
+        )?,
+        column_chunks: read_text_rows(
+            conn,
+            COLUMN_CHUNK_COLUMNS,
+            &format!("parquet_metadata({})", files),
+            Some("file_name, row_group_id, column_id"),
+        )?,
+    })
+}
+
+// Selects `columns` from the table function as text, with NULL shown as an empty
+// string, in the order the function returns them unless `order_by` is given.
+fn read_text_rows(
+    conn: &Connection,
+    columns: &[&str],
+    function: &str,
+    order_by: Option<&str>,
+) -> Result<Vec<Vec<String>>, Error> {
+    let select: Vec<String> = columns
+        .iter()
+        .map(|column| format!("coalesce(CAST({} AS VARCHAR), '')", column))
+        .collect();
+    let mut query = format!("SELECT {} FROM {}", select.join(", "), function);
+    if let Some(order_by) = order_by {
+        query.push_str(&format!(" ORDER BY {}", order_by));
+    }
+
+    let mut stmt = conn
+        .prepare(&query)
+        .with_context(|| format!("Failed to create metadata context with '{}'", function))?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            (0..columns.len())
+                .map(|i| row.get::<_, String>(i))
+                .collect::<Result<Vec<String>, _>>()
+        })
+        .with_context(|| "Failed to execute metadata query".to_string())?
+        .collect::<Result<Vec<Vec<String>>, _>>()
+        .with_context(|| "Failed to read the metadata".to_string())?;
+
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_parquet_metadata() -> Result<(), Error> {
+        let conn = Connection::open_in_memory()?;
+        let metadata = read_parquet_metadata(&conn, "'test_data/AAPL_minute.parquet'")?;
+
+        assert_eq!(metadata.files.len(), 1);
+        assert_eq!(metadata.files[0].len(), FILE_COLUMNS.len());
+        assert_eq!(metadata.files[0][0], "test_data/AAPL_minute.parquet");
+
+        // the root schema element is followed by one element per column
+        assert!(metadata.schema.len() > 1);
+        assert!(metadata
+            .schema
+            .iter()
+            .all(|row| row.len() == SCHEMA_COLUMNS.len()));
+
+        // the row counts of the row groups add up to the row count of the file
+        let row_count: i64 = metadata.files[0][2].parse()?;
+        let mut row_group_rows: Vec<(String, i64)> = Vec::new();
+        for chunk in &metadata.column_chunks {
+            let row_group = (chunk[1].clone(), chunk[2].parse()?);
+            if !row_group_rows.contains(&row_group) {
+                row_group_rows.push(row_group);
+            }
+        }
+        let total: i64 = row_group_rows.iter().map(|(_, rows)| rows).sum();
+        assert_eq!(total, row_count);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_parquet_metadata_of_missing_file() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert!(read_parquet_metadata(&conn, "'test_data/does_not_exist.parquet'").is_err());
+    }
+}
@@ -0,0 +1,92 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Hands out increasing generation numbers for table requests so that only the
+/// most recently requested page is ever shown.
+///
+/// Every page, sort, page size and open request spawns its own task and the tasks
+/// can finish in any order. A task holding a stale `RequestTicket` must drop its
+/// result instead of applying it to the ui.
+#[derive(Clone, Default)]
+pub struct RequestTracker {
+    latest: Arc<AtomicU64>,
+}
+
+impl RequestTracker {
+    pub fn new() -> RequestTracker {
+        RequestTracker::default()
+    }
+
+    /// Starts a new request, making every earlier ticket stale.
+    pub fn begin(&self) -> RequestTicket {
+        let generation = self.latest.fetch_add(1, Ordering::SeqCst) + 1;
+        RequestTicket {
+            tracker: self.clone(),
+            generation,
+        }
+    }
+}
+
+/// The generation of a single table request.
+#[derive(Clone)]
+pub struct RequestTicket {
+    tracker: RequestTracker,
+    generation: u64,
+}
+
+impl RequestTicket {
+    /// Returns true if no newer request has been started since this one.
+    pub fn is_current(&self) -> bool {
+        self.tracker.latest.load(Ordering::SeqCst) == self.generation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latest_ticket_is_current() {
+        let tracker = RequestTracker::new();
+        let first = tracker.begin();
+        assert!(first.is_current());
+
+        let second = tracker.begin();
+        assert!(!first.is_current());
+        assert!(second.is_current());
+        assert!(second.generation > first.generation);
+    }
+
+    #[test]
+    fn test_out_of_order_completions() {
+        let tracker = RequestTracker::new();
+        let mut applied: Vec<&str> = Vec::new();
+
+        // a slow sort request followed by a fast page request
+        let sort_request = tracker.begin();
+        let page_request = tracker.begin();
+
+        // the page request finishes first and is applied
+        if page_request.is_current() {
+            applied.push("page 2");
+        }
+        // the sort request finishes last and must not overwrite the page
+        if sort_request.is_current() {
+            applied.push("sorted page 1");
+        }
+
+        assert_eq!(applied, vec!["page 2"]);
+    }
+
+    #[test]
+    fn test_clones_share_generations() {
+        let tracker = RequestTracker::new();
+        let ticket = tracker.begin();
+
+        // the ui callbacks each hold their own clone of the tracker
+        let other = tracker.clone();
+        other.begin();
+
+        assert!(!ticket.is_current());
+    }
+}
@@ -0,0 +1,971 @@
+use duckdb::{Connection, InterruptHandle};
+use slint::{SharedString, TableColumn};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::csv_options::CsvOptions;
+use crate::error::{FailedQuery, LoaderError};
+use crate::filter::ColumnFilter;
+use crate::model::{ColumnKind, ColumnSchema, Filename, TableView};
+use crate::parquet_metadata::{read_parquet_metadata, ParquetMetadata};
+use crate::settings::DuckDbSettings;
+use crate::sql::{quote_identifier, scan, string_literal};
+use crate::utils::{
+    escape_glob, excel_sheet_names, get_file_format, hive_partition_keys, is_dataset_path,
+    is_glob_pattern, is_sqlite_file,
+};
+use anyhow::{Context, Error};
+
+/// The canceller for the currently opened file. It is shared with the ui because
+/// the loader thread that owns the session is busy while a query runs.
+pub type SharedCanceller = Arc<Mutex<Option<QueryCanceller>>>;
+
+// The name a database file is attached under
+const ATTACHED_DATABASE: &str = "source_db";
+
+/// The name of the view the opened file or selected table is exposed as in the sql editor
+pub const DATA_VIEW: &str = "data";
+
+// Overrides the directory that bundled DuckDB extensions are loaded from
+const EXTENSION_DIR_VARIABLE: &str = "DUCKDB_VIEWER_EXTENSION_DIR";
+
+// The column that numbers the rows of a scan that can't number them itself
+const ROW_NUMBER_COLUMN: &str = "__viewer_row_number";
+
+/// A table or view of an attached database file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DatabaseTable {
+    pub schema: String,
+    pub name: String,
+    pub is_view: bool,
+}
+
+impl DatabaseTable {
+    /// The name shown in the table picker, e.g. `main.orders` or `main.totals (view)`.
+    pub fn display_name(&self) -> String {
+        if self.is_view {
+            format!("{}.{} (view)", self.schema, self.name)
+        } else {
+            format!("{}.{}", self.schema, self.name)
+        }
+    }
+
+    // The fully qualified name used in the FROM clause
+    fn source(&self) -> String {
+        format!(
+            "{}.{}.{}",
+            quote_identifier(ATTACHED_DATABASE),
+            quote_identifier(&self.schema),
+            quote_identifier(&self.name)
+        )
+    }
+}
+
+/// Interrupts the query running on the connection of a `FileSession`.
+#[derive(Clone)]
+pub struct QueryCanceller {
+    interrupt: Arc<InterruptHandle>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl QueryCanceller {
+    /// Interrupts the running query and flags it as cancelled so the loader can
+    /// tell a cancellation apart from a failed query.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.interrupt.interrupt();
+    }
+}
+
+/// A sheet of an opened Excel workbook and the row its column names are read from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExcelSheet {
+    pub name: String,
+    /// The 1-indexed row holding the column names, or 0 if the sheet has no header row
+    pub header_row: u32,
+}
+
+impl ExcelSheet {
+    // The read_xlsx call used in the FROM clause. Rows above the header row are skipped
+    fn source(&self, filename: &Filename) -> String {
+        let first_row = self.header_row.max(1);
+        scan(
+            "read_xlsx",
+            &escape_glob(&filename.0),
+            &format!(
+                ", sheet = {}, header = {}, range = 'A{}:XFD1048576', stop_at_empty = true",
+                string_literal(&self.name),
+                self.header_row > 0,
+                first_row
+            ),
+        )
+    }
+}
+
+/// The dialect and column types DuckDB's csv sniffer detected for a csv file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CsvDialect {
+    pub delimiter: String,
+    pub quote: String,
+    pub escape: String,
+    pub new_line: String,
+    pub skip_rows: String,
+    pub has_header: bool,
+    pub date_format: String,
+    pub timestamp_format: String,
+    /// The inferred name and type of each column
+    pub columns: Vec<(String, String)>,
+    /// The `FROM read_csv(...)` call that reads the file with the detected dialect
+    pub read_csv: String,
+}
+
+// How an opened file is read
+enum FileKind {
+    // a csv file read with the import options
+    Csv,
+    // a parquet file, which also has metadata to inspect
+    Parquet,
+    // a flat file read with the given scan function
+    Scan(&'static str),
+    // a flat file read with a scan function of a bundled extension
+    ExtensionScan {
+        extension: &'static str,
+        function: &'static str,
+    },
+    // a database file browsed one table at a time
+    Database,
+    // an Excel workbook browsed one sheet at a time
+    Workbook,
+    // a directory or glob of parquet files read as one dataset
+    Dataset,
+}
+
+/// An opened file and the DuckDB state needed to page through it.
+///
+/// The connection, the scan expression and the column schema are created once when
+/// the file is opened and reused for every page, sort and page size request.
+/// The total row count is computed on the first page request and cached until
+/// [`FileSession::invalidate_row_count`] is called.
+pub struct FileSession {
+    pub filename: Filename,
+    conn: Connection,
+    source: String,
+    columns: Vec<TableColumn>,
+    schema: Vec<ColumnSchema>,
+    // the filter bar predicates applied to the source
+    filters: Vec<ColumnFilter>,
+    // the search box text, matched against every column
+    search: String,
+    row_count: Option<i32>,
+    view: Option<TableView>,
+    cancelled: Arc<AtomicBool>,
+    tables: Vec<DatabaseTable>,
+    sheets: Vec<String>,
+    // the arguments of the csv scan, shared with the sniffer
+    csv_arguments: Option<String>,
+    // the quoted file name or glob passed to the parquet metadata functions
+    parquet_files: Option<String>,
+    // the scan numbering each row by its position in its file, for parquet files
+    numbered_scan: Option<NumberedSource>,
+    // the query run in the sql editor, which replaces the source until it is cleared
+    query: Option<QuerySource>,
+}
+
+/// A source with extra columns after the columns of its schema that number its rows in
+/// the order they are read, used to break ties in a sort.
+#[derive(Clone, Debug)]
+pub struct NumberedSource {
+    pub source: String,
+    /// The extra columns, in the order the rows are sorted on them.
+    pub columns: Vec<&'static str>,
+}
+
+impl NumberedSource {
+    // `scan` with the columns its options add moved after the columns of the schema,
+    // the scan puts them before the hive partition keys
+    fn scan(scan: String, columns: Vec<&'static str>) -> Self {
+        let quoted = columns
+            .iter()
+            .map(|column| quote_identifier(column))
+            .collect::<Vec<_>>()
+            .join(", ");
+        NumberedSource {
+            source: format!(
+                "(SELECT * EXCLUDE ({}), {} FROM {}) AS numbered",
+                quoted, quoted, scan
+            ),
+            columns,
+        }
+    }
+}
+
+// A query over the `data` view and the table header and schema of each of its columns
+struct QuerySource {
+    source: String,
+    columns: Vec<TableColumn>,
+    schema: Vec<ColumnSchema>,
+}
+
+/// The query, filters, search and page that `set_query` replaced, put back with
+/// `restore_query` when the new query fails to load.
+pub struct PreviousQuery {
+    query: Option<QuerySource>,
+    filters: Vec<ColumnFilter>,
+    search: String,
+    view: Option<TableView>,
+}
+
+impl FileSession {
+    /// Opens a connection for `filename`, resolves the scan function from the file
+    /// extension and reads the column names and types.
+    ///
+    /// The connection uses the memory limit, threads and temp directory of `settings`.
+    /// Csv files are read with `csv_options`, which are ignored for other files.
+    /// Csv and json files compressed with gzip (`.gz`) or zstd (`.zst`) are
+    /// decompressed while they are read.
+    ///
+    /// Directories and glob patterns are read as one parquet dataset with the hive
+    /// partition keys as extra columns.
+    ///
+    /// DuckDB and SQLite database files are attached read-only and their first table
+    /// is selected. Excel workbooks open on their first sheet with the column names
+    /// read from the first row.
+    ///
+    /// Once the file is attached and the extensions it needs are loaded, the connection
+    /// is locked down: it can only read the opened file or dataset, can't write files,
+    /// attach databases or install extensions, and its settings can't be changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// * The file extension is not supported
+    /// * DuckDB connection fails or rejects one of the `settings`
+    /// * A database file can't be attached or has no tables
+    /// * The SQLite, Excel or nanoarrow extension is not bundled with the application
+    /// * The sheets of a workbook can't be listed
+    /// * No parquet files match a directory or glob pattern
+    /// * The schema query fails
+    pub fn open(
+        filename: Filename,
+        csv_options: &CsvOptions,
+        settings: &DuckDbSettings,
+    ) -> Result<FileSession, Error> {
+        // Get the file extension and determine the appropriate scan function
+        // `data.csv.gz` is read as a gzip compressed csv file
+        let (extension, compression) = get_file_format(&filename.0);
+        let file_kind = match extension.as_str() {
+            // checked first since a glob such as `dt=*/*.parquet` also has an extension
+            _ if is_dataset_path(&filename.0) => FileKind::Dataset,
+            // only the csv and json readers decompress files
+            "csv" | "tsv" => FileKind::Csv,
+            // read_json_auto detects both newline-delimited and array formats
+            "json" | "jsonl" | "ndjson" => FileKind::Scan("read_json_auto"),
+            _ if compression.is_some() => {
+                return Err(LoaderError::UnsupportedFormat {
+                    filename: filename.0.to_string(),
+                }
+                .into());
+            }
+            "parquet" => FileKind::Parquet,
+            // Arrow IPC files, Feather v2 is the same format
+            "arrow" | "feather" | "ipc" => FileKind::ExtensionScan {
+                extension: "nanoarrow",
+                function: "read_arrow",
+            },
+            // database files are attached and browsed one table at a time
+            "duckdb" | "db" | "sqlite" | "sqlite3" => FileKind::Database,
+            "xlsx" => FileKind::Workbook,
+            _ => {
+                return Err(LoaderError::UnsupportedFormat {
+                    filename: filename.0.to_string(),
+                }
+                .into());
+            }
+        };
+
+        let conn = Connection::open_in_memory()
+            .with_context(|| "Failed to set up duckdb connection".to_string())?;
+        settings.apply(&conn)?;
+
+        let mut tables = Vec::new();
+        let mut sheets = Vec::new();
+        let mut partition_keys = Vec::new();
+        let mut csv_arguments = None;
+        let mut parquet_files = None;
+        let mut numbered_scan = None;
+        // a file name such as `sales[1].parquet` would be expanded as a glob by the scans
+        let scan_path = escape_glob(&filename.0);
+        // the files the locked down connection may read. DuckDB checks the path a scan
+        // is given, which is the escaped one for a name with glob characters
+        let mut allowed_paths = vec![filename.0.to_string()];
+        if scan_path != filename.0.as_str() {
+            allowed_paths.push(scan_path.clone());
+        }
+        let mut allowed_directories = Vec::new();
+        if !settings.temp_directory.is_empty() {
+            // sorts that don't fit into the memory limit spill to it
+            allowed_directories.push(settings.temp_directory.clone());
+        }
+        let source = match file_kind {
+            FileKind::Csv => {
+                let compression = compression
+                    .map(|compression| format!(", compression = {}", string_literal(&compression)))
+                    .unwrap_or_default();
+                let arguments = format!(
+                    "{}{}{}",
+                    string_literal(&scan_path),
+                    compression,
+                    csv_options.scan_arguments()
+                );
+                let source = format!("read_csv_auto({})", arguments);
+                csv_arguments = Some(arguments);
+                source
+            }
+            FileKind::Parquet => {
+                parquet_files = Some(string_literal(&scan_path));
+                numbered_scan = Some(NumberedSource::scan(
+                    scan("parquet_scan", &scan_path, ", file_row_number = true"),
+                    vec!["file_row_number"],
+                ));
+                scan("parquet_scan", &scan_path, "")
+            }
+            FileKind::Scan(scan_function) => match compression {
+                Some(compression) => scan(
+                    scan_function,
+                    &scan_path,
+                    &format!(", compression = {}", string_literal(&compression)),
+                ),
+                None => scan(scan_function, &scan_path, ""),
+            },
+            FileKind::ExtensionScan {
+                extension,
+                function,
+            } => {
+                load_bundled_extension(&conn, extension)?;
+                scan(function, &scan_path, "")
+            }
+            FileKind::Database => {
+                tables = attach_database(&conn, &filename)?;
+                tables
+                    .first()
+                    .map(|table| table.source())
+                    .ok_or_else(|| Error::msg("The database has no tables or views"))?
+            }
+            FileKind::Workbook => {
+                load_bundled_extension(&conn, "excel")?;
+                sheets = excel_sheet_names(&filename.0)?;
+                let first_sheet = ExcelSheet {
+                    name: sheets
+                        .first()
+                        .cloned()
+                        .ok_or_else(|| Error::msg("The workbook has no sheets"))?,
+                    header_row: 1,
+                };
+                first_sheet.source(&filename)
+            }
+            FileKind::Dataset => {
+                let pattern = dataset_pattern(&filename.0);
+                let files = list_dataset_files(&conn, &pattern)?;
+                partition_keys = hive_partition_keys(&files);
+                allowed_paths.extend(files);
+                allowed_directories.extend(dataset_directory(&pattern));
+                parquet_files = Some(string_literal(&pattern));
+                numbered_scan = Some(NumberedSource::scan(
+                    scan(
+                        "read_parquet",
+                        &pattern,
+                        ", hive_partitioning = true, union_by_name = true, \
+                         filename = true, file_row_number = true",
+                    ),
+                    vec!["filename", "file_row_number"],
+                ));
+                scan(
+                    "read_parquet",
+                    &pattern,
+                    ", hive_partitioning = true, union_by_name = true",
+                )
+            }
+        };
+        lock_down(&conn, &allowed_paths, &allowed_directories)?;
+        let (columns, schema) = read_columns(&conn, &source, &filename, &partition_keys)?;
+        create_data_view(&conn, &source)?;
+
+        Ok(FileSession {
+            filename,
+            conn,
+            source,
+            columns,
+            schema,
+            filters: Vec::new(),
+            search: String::new(),
+            row_count: None,
+            view: None,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            tables,
+            sheets,
+            csv_arguments,
+            parquet_files,
+            numbered_scan,
+            query: None,
+        })
+    }
+
+    /// The tables and views of an opened database file. Empty for flat files.
+    pub fn tables(&self) -> &[DatabaseTable] {
+        &self.tables
+    }
+
+    /// Switches to the table at `index` of `tables()` and reads its column names and types.
+    pub fn select_table(&mut self, index: usize) -> Result<(), Error> {
+        let table = self
+            .tables
+            .get(index)
+            .ok_or_else(|| Error::msg(format!("No table at index {}", index)))?;
+        let source = table.source();
+        self.set_source(source)
+    }
+
+    /// The sheet names of an opened Excel workbook. Empty for other files.
+    pub fn sheets(&self) -> &[String] {
+        &self.sheets
+    }
+
+    /// Switches to the sheet at `index` of `sheets()`, reading the column names from
+    /// `header_row`. The sheet stays selected for every following page and sort.
+    pub fn select_sheet(&mut self, index: usize, header_row: u32) -> Result<(), Error> {
+        let name = self
+            .sheets
+            .get(index)
+            .ok_or_else(|| Error::msg(format!("No sheet at index {}", index)))?;
+        let sheet = ExcelSheet {
+            name: name.clone(),
+            header_row,
+        };
+        let source = sheet.source(&self.filename);
+        self.set_source(source)
+    }
+
+    /// Runs `sql` over the `data` view instead of the opened file, or goes back to the
+    /// opened file when `sql` is `None`. Every following page and sort reads the query.
+    ///
+    /// Only a single query can be run. It is wrapped in a subquery, which DuckDB
+    /// rejects for any statement that is not a query.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the sql is empty, is not a single query or fails
+    pub fn set_query(&mut self, sql: Option<&str>) -> Result<PreviousQuery, Error> {
+        let query = match sql {
+            Some(sql) => {
+                // a trailing semicolon is part of how queries are usually typed
+                let sql = sql.trim().trim_end_matches(';').trim_end();
+                if sql.is_empty() {
+                    return Err(Error::msg("The query is empty"));
+                }
+                let source = format!("({}\n) AS query", sql);
+                let (columns, schema) = read_columns(&self.conn, &source, &self.filename, &[])?;
+                Some(QuerySource {
+                    source,
+                    columns,
+                    schema,
+                })
+            }
+            None => None,
+        };
+
+        self.invalidate_row_count();
+        Ok(PreviousQuery {
+            query: std::mem::replace(&mut self.query, query),
+            filters: std::mem::take(&mut self.filters),
+            search: std::mem::take(&mut self.search),
+            view: self.view.take(),
+        })
+    }
+
+    /// Puts back the query, filters, search and page replaced by `set_query`.
+    pub fn restore_query(&mut self, previous: PreviousQuery) {
+        self.invalidate_row_count();
+        self.query = previous.query;
+        self.filters = previous.filters;
+        self.search = previous.search;
+        self.view = previous.view;
+    }
+
+    /// The filter bar predicates applied to the source, combined with AND.
+    pub fn filters(&self) -> &[ColumnFilter] {
+        &self.filters
+    }
+
+    /// Replaces the filters applied to the source and returns the previous ones.
+    /// Every following page, sort and row count reads the filtered rows.
+    pub fn set_filters(&mut self, filters: Vec<ColumnFilter>) -> Vec<ColumnFilter> {
+        self.invalidate_row_count();
+        std::mem::replace(&mut self.filters, filters)
+    }
+
+    /// The search box text, an empty string when nothing is searched.
+    pub fn search(&self) -> &str {
+        &self.search
+    }
+
+    /// Replaces the search box text and returns the previous one. Every following
+    /// page, sort and row count reads the rows that mention it in any column.
+    pub fn set_search(&mut self, search: String) -> String {
+        self.invalidate_row_count();
+        std::mem::replace(&mut self.search, search)
+    }
+
+    // Switches to another table or sheet of the opened file. The `data` view follows
+    // it and any query from the sql editor, the filters and the search are cleared
+    fn set_source(&mut self, source: String) -> Result<(), Error> {
+        let (columns, schema) = read_columns(&self.conn, &source, &self.filename, &[])?;
+        create_data_view(&self.conn, &source)?;
+
+        self.source = source;
+        self.numbered_scan = None;
+        self.columns = columns;
+        self.schema = schema;
+        self.query = None;
+        self.filters.clear();
+        self.search.clear();
+        self.invalidate_row_count();
+        self.view = None;
+        Ok(())
+    }
+
+    /// Runs DuckDB's csv sniffer on an opened csv file with the file's import options.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the opened file is not a csv file or the sniffer fails
+    pub fn sniff_csv(&self) -> Result<CsvDialect, Error> {
+        let arguments = self
+            .csv_arguments
+            .as_ref()
+            .ok_or_else(|| Error::msg("The opened file is not a csv file"))?;
+
+        let query = format!(
+            "SELECT Delimiter, Quote, Escape, NewLineDelimiter, SkipRows::VARCHAR,
+                    HasHeader, coalesce(DateFormat, ''), coalesce(TimestampFormat, ''), Prompt
+             FROM sniff_csv({})",
+            arguments
+        );
+        let mut dialect = self
+            .conn
+            .query_row(&query, [], |row| {
+                Ok(CsvDialect {
+                    delimiter: row.get(0)?,
+                    quote: row.get(1)?,
+                    escape: row.get(2)?,
+                    new_line: row.get(3)?,
+                    skip_rows: row.get(4)?,
+                    has_header: row.get(5)?,
+                    date_format: row.get(6)?,
+                    timestamp_format: row.get(7)?,
+                    columns: Vec::new(),
+                    read_csv: row.get::<_, String>(8)?.trim().to_string(),
+                })
+            })
+            .with_context(|| format!("Failed to sniff '{}'", self.filename.0))?;
+
+        // the inferred column names and types
+        let query = format!(
+            "SELECT c.name, c.type FROM (SELECT unnest(Columns) AS c FROM sniff_csv({}))",
+            arguments
+        );
+        let mut stmt = self
+            .conn
+            .prepare(&query)
+            .with_context(|| format!("Failed to sniff the columns of '{}'", self.filename.0))?;
+        dialect.columns = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .with_context(|| "Failed to execute column sniff query".to_string())?
+            .collect::<Result<Vec<(String, String)>, _>>()
+            .with_context(|| "Failed to read the sniffed columns".to_string())?;
+
+        Ok(dialect)
+    }
+
+    /// Reads the file, schema, row group and column chunk metadata of an opened
+    /// parquet file or of every file of a parquet dataset.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the opened file is not parquet or the metadata can't be read
+    pub fn inspect_parquet(&self) -> Result<ParquetMetadata, Error> {
+        let files = self
+            .parquet_files
+            .as_ref()
+            .ok_or_else(|| Error::msg("The opened file is not a parquet file"))?;
+        read_parquet_metadata(&self.conn, files)
+            .with_context(|| format!("Failed to inspect '{}'", self.filename.0))
+    }
+
+    pub fn conn(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// The table expression used in the FROM clause, e.g. `parquet_scan('data.parquet')`,
+    /// or the query from the sql editor as a subquery.
+    pub fn source(&self) -> &str {
+        match &self.query {
+            Some(query) => &query.source,
+            None => &self.source,
+        }
+    }
+
+    /// The table header for each column, named `<column>\n(<type>)`.
+    pub fn columns(&self) -> &[TableColumn] {
+        match &self.query {
+            Some(query) => &query.columns,
+            None => &self.columns,
+        }
+    }
+
+    /// The name and kind of each column, in the order of `columns()`.
+    pub fn schema(&self) -> &[ColumnSchema] {
+        match &self.query {
+            Some(query) => &query.schema,
+            None => &self.schema,
+        }
+    }
+
+    /// The source with its rows numbered in the order they are read, to break ties in
+    /// a sort. Parquet scans number each row by its position in its file, and datasets
+    /// by the name of the file first. Other scans can't provide a row number, so their
+    /// rows are numbered with `row_number()` as they are read.
+    ///
+    /// `None` for a query from the sql editor, whose rows are not read in the same
+    /// order every time.
+    pub fn numbered_source(&self) -> Option<NumberedSource> {
+        if self.query.is_some() {
+            return None;
+        }
+        Some(match &self.numbered_scan {
+            Some(numbered_scan) => numbered_scan.clone(),
+            None => NumberedSource {
+                source: format!(
+                    "(SELECT *, row_number() OVER () AS {} FROM {}) AS numbered",
+                    quote_identifier(ROW_NUMBER_COLUMN),
+                    self.source
+                ),
+                columns: vec![ROW_NUMBER_COLUMN],
+            },
+        })
+    }
+
+    /// The cached total row count, if it has been computed.
+    pub fn row_count(&self) -> Option<i32> {
+        self.row_count
+    }
+
+    pub fn set_row_count(&mut self, row_count: i32) {
+        self.row_count = Some(row_count);
+    }
+
+    /// The page and sort of the last page that was loaded successfully.
+    pub fn view(&self) -> Option<TableView> {
+        self.view.clone()
+    }
+
+    pub fn set_view(&mut self, view: TableView) {
+        self.view = Some(view);
+    }
+
+    /// A canceller that interrupts queries running on this session's connection.
+    pub fn canceller(&self) -> QueryCanceller {
+        QueryCanceller {
+            interrupt: self.conn.interrupt_handle(),
+            cancelled: self.cancelled.clone(),
+        }
+    }
+
+    /// Returns true if a query was cancelled since the last call, and clears the flag.
+    pub fn take_cancelled(&self) -> bool {
+        self.cancelled.swap(false, Ordering::SeqCst)
+    }
+
+    /// Forgets the cached row count. Must be called whenever the rows selected by
+    /// `source` change, e.g. when a filter or query is applied.
+    pub fn invalidate_row_count(&mut self) {
+        self.row_count = None;
+    }
+}
+
+// Exposes `source` as the `data` view that queries in the sql editor read from.
+fn create_data_view(conn: &Connection, source: &str) -> Result<(), Error> {
+    conn.execute(
+        &format!(
+            "CREATE OR REPLACE TEMP VIEW {} AS SELECT * FROM {}",
+            quote_identifier(DATA_VIEW),
+            source
+        ),
+        [],
+    )
+    .with_context(|| format!("Failed to create the {} view", DATA_VIEW))?;
+    Ok(())
+}
+
+// Attaches the database file read-only and lists its tables and views.
+// SQLite files are read through the bundled SQLite scanner.
+fn attach_database(conn: &Connection, filename: &Filename) -> Result<Vec<DatabaseTable>, Error> {
+    let options = if is_sqlite_file(&filename.0) {
+        load_bundled_extension(conn, "sqlite_scanner")?;
+        "TYPE SQLITE, READ_ONLY"
+    } else {
+        "READ_ONLY"
+    };
+
+    conn.execute(
+        &format!(
+            "ATTACH {} AS {} ({})",
+            string_literal(&filename.0),
+            quote_identifier(ATTACHED_DATABASE),
+            options
+        ),
+        [],
+    )
+    .with_context(|| format!("Failed to attach database '{}'", filename.0))?;
+
+    // information_schema lists the tables of every attached database type
+    let query = format!(
+        "SELECT table_schema, table_name, table_type = 'VIEW' FROM information_schema.tables
+         WHERE table_catalog = {}
+         ORDER BY 1, 2",
+        string_literal(ATTACHED_DATABASE)
+    );
+    let mut stmt = conn
+        .prepare(&query)
+        .with_context(|| format!("Failed to list the tables of '{}'", filename.0))?;
+
+    let tables = stmt
+        .query_map([], |row| {
+            Ok(DatabaseTable {
+                schema: row.get(0)?,
+                name: row.get(1)?,
+                is_view: row.get(2)?,
+            })
+        })
+        .with_context(|| "Failed to execute table list query".to_string())?
+        .collect::<Result<Vec<DatabaseTable>, _>>()
+        .with_context(|| "Failed to read the table list".to_string())?;
+
+    Ok(tables)
+}
+
+// Turns the connection into a read-only sandbox once the app has attached the opened
+// database and loaded the extensions it needs. Queries can only read `allowed_paths`
+// and the files under `allowed_directories`. COPY ... TO, ATTACH, INSTALL and LOAD
+// are rejected, extensions are never downloaded or loaded on demand, and locking the
+// configuration keeps a query from turning any of this back on.
+fn lock_down(
+    conn: &Connection,
+    allowed_paths: &[String],
+    allowed_directories: &[String],
+) -> Result<(), Error> {
+    let paths: Vec<String> = allowed_paths
+        .iter()
+        .map(|path| string_literal(path))
+        .collect();
+    let directories: Vec<String> = allowed_directories
+        .iter()
+        .map(|directory| string_literal(directory))
+        .collect();
+    // the allowed files must be set while external access is still enabled
+    conn.execute_batch(&format!(
+        "SET autoinstall_known_extensions = false;
+         SET autoload_known_extensions = false;
+         SET allowed_paths = [{}];
+         SET allowed_directories = [{}];
+         SET enable_external_access = false;
+         SET lock_configuration = true;",
+        paths.join(", "),
+        directories.join(", ")
+    ))
+    .with_context(|| "Failed to lock down the duckdb connection".to_string())?;
+    Ok(())
+}
+
+// Loads an extension from the bundled extension directory. Autoloading and
+// autoinstalling are turned off first so DuckDB never downloads an extension.
+fn load_bundled_extension(conn: &Connection, name: &str) -> Result<(), Error> {
+    let path = bundled_extension_path(name)?;
+    if !path.is_file() {
+        return Err(Error::msg(format!(
+            "The {} extension is not bundled at '{}'",
+            name,
+            path.display()
+        )));
+    }
+
+    conn.execute_batch(
+        "SET autoinstall_known_extensions = false; SET autoload_known_extensions = false;",
+    )
+    .with_context(|| "Failed to disable extension autoloading".to_string())?;
+    conn.execute(
+        &format!("LOAD {}", string_literal(&path.display().to_string())),
+        [],
+    )
+    .with_context(|| format!("Failed to load the {} extension", name))?;
+
+    Ok(())
+}
+
+// The path the extension `name` is loaded from, which may not exist.
+fn bundled_extension_path(name: &str) -> Result<PathBuf, Error> {
+    Ok(bundled_extension_dir()?.join(format!("{}.duckdb_extension", name)))
+}
+
+// The `extensions` directory next to the executable, unless overridden by the
+// DUCKDB_VIEWER_EXTENSION_DIR environment variable.
+fn bundled_extension_dir() -> Result<PathBuf, Error> {
+    if let Ok(dir) = env::var(EXTENSION_DIR_VARIABLE) {
+        return Ok(PathBuf::from(dir));
+    }
+    let exe = env::current_exe().with_context(|| "Failed to locate the executable".to_string())?;
+    let dir = exe
+        .parent()
+        .ok_or_else(|| Error::msg("Failed to locate the executable directory"))?;
+    Ok(dir.join("extensions"))
+}
+
+// The glob of the parquet files of a dataset. Directories are searched recursively.
+fn dataset_pattern(filename: &str) -> String {
+    if Path::new(filename).is_dir() {
+        format!("{}/**/*.parquet", filename.trim_end_matches(['/', '\\']))
+    } else {
+        filename.to_string()
+    }
+}
+
+// The directory a dataset glob searches, e.g. `lake` for `lake/dt=*/*.parquet`, or
+// `None` if the glob starts with a wildcard.
+fn dataset_directory(pattern: &str) -> Option<String> {
+    let mut directory = PathBuf::new();
+    for component in Path::new(pattern).components() {
+        let component = component.as_os_str().to_string_lossy();
+        if is_glob_pattern(&component) {
+            break;
+        }
+        directory.push(component.as_ref());
+    }
+    if directory.as_os_str().is_empty() || directory == Path::new(pattern) {
+        return None;
+    }
+    Some(directory.to_string_lossy().to_string())
+}
+
+// Lists the files matching the glob `pattern` of a dataset.
+fn list_dataset_files(conn: &Connection, pattern: &str) -> Result<Vec<String>, Error> {
+    let query = format!("SELECT file FROM {}", scan("glob", pattern, ""));
+    let mut stmt = conn
+        .prepare(&query)
+        .with_context(|| format!("Failed to list the files of '{}'", pattern))?;
+
+    let files = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .with_context(|| "Failed to execute file list query".to_string())?
+        .collect::<Result<Vec<String>, _>>()
+        .with_context(|| "Failed to read the file list".to_string())?;
+
+    if files.is_empty() {
+        return Err(Error::msg(format!("No parquet files match '{}'", pattern)));
+    }
+
+    Ok(files)
+}
+
+// Runs a LIMIT 1 query to get the column names and types of the source, returned as
+// table headers and as the schema used by filters.
+// Columns named in `partition_keys` are marked as partitions in their header.
+fn read_columns(
+    conn: &Connection,
+    source: &str,
+    filename: &Filename,
+    partition_keys: &[String],
+) -> Result<(Vec<TableColumn>, Vec<ColumnSchema>), Error> {
+    let query = format!("SELECT * FROM {} LIMIT 1", source);
+
+    let mut stmt = conn.prepare(&query).with_context(|| {
+        FailedQuery::new(
+            format!("Failed to create metadata context with '{}'", filename.0),
+            &query,
+        )
+    })?;
+
+    let _ = stmt
+        .query([])
+        .with_context(|| FailedQuery::new("Failed to execute metadata query", &query))?;
+
+    let column_count = stmt.column_count();
+
+    // the DuckDB types, which keep the precision and scale the arrow type names drop
+    let describe = format!("DESCRIBE SELECT * FROM {}", source);
+    let mut describe_stmt = conn
+        .prepare(&describe)
+        .with_context(|| FailedQuery::new("Failed to describe the columns", &describe))?;
+    let sql_types = describe_stmt
+        .query_map([], |row| row.get::<_, String>(1))
+        .with_context(|| FailedQuery::new("Failed to execute describe query", &describe))?
+        .collect::<Result<Vec<String>, _>>()
+        .with_context(|| FailedQuery::new("Failed to read the column types", &describe))?;
+
+    // get the column names and types
+    let mut column_names: Vec<TableColumn> = Vec::new();
+    let mut schema: Vec<ColumnSchema> = Vec::new();
+    for i in 0..column_count {
+        let column_name = stmt
+            .column_name(i)
+            .with_context(|| format!("Failed to get the column name at index '{}'", i))?
+            .to_string();
+
+        let column_type = stmt
+            .column_type(i)
+            .to_string()
+            .split('(')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_string();
+
+        let display_name = if partition_keys.contains(&column_name) {
+            format!("{}\n({}, partition)", column_name, column_type)
+        } else {
+            format!("{}\n({})", column_name, column_type)
+        };
+        let mut table_column = TableColumn::default();
+        table_column.title = SharedString::from(display_name.as_str());
+        table_column.min_width = 50.0;
+        table_column.width = 100.0;
+        column_names.push(table_column);
+        let sql_type = sql_types.get(i).cloned().ok_or_else(|| {
+            Error::msg(format!("No DuckDB type for the column '{}'", column_name))
+        })?;
+        schema.push(ColumnSchema {
+            kind: column_kind(&column_type),
+            name: column_name,
+            sql_type,
+        });
+    }
+
+    Ok((column_names, schema))
+}
+
+// The kind of a column from the name of its arrow type, e.g. `Int64` or `Timestamp`.
+fn column_kind(type_name: &str) -> ColumnKind {
+    match type_name {
+        "Utf8" | "LargeUtf8" | "Utf8View" => ColumnKind::Text,
+        "Date32" | "Date64" => ColumnKind::Date,
+        "Timestamp" => ColumnKind::Timestamp,
+        _ if type_name.starts_with("Int")
+            || type_name.starts_with("UInt")
+            || type_name.starts_with("Float")
+            || type_name.starts_with("Decimal") =>
+        {
+            ColumnKind::Number
+        }
+        _ => ColumnKind::Other,
+    }
+}
@@ -0,0 +1,235 @@
+use anyhow::{Context, Error};
+use duckdb::Connection;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::sql::string_literal;
+
+// The directory of the settings file under the user's configuration directory
+const CONFIG_DIRECTORY: &str = "slint_duckdb_viewer";
+const CONFIG_FILE: &str = "settings.conf";
+
+/// The resources DuckDB may use, applied to the connection of every opened file.
+///
+/// Empty strings and 0 leave the setting to DuckDB, which defaults to 80% of the
+/// memory, a thread per core and a `.tmp` directory next to the database.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DuckDbSettings {
+    /// e.g. `4GB` or `75%`
+    pub memory_limit: String,
+    pub threads: u32,
+    /// Where sorts and aggregates that don't fit into the memory limit spill to
+    pub temp_directory: String,
+}
+
+impl DuckDbSettings {
+    /// Reads the settings file at `path`, one `key = value` line per setting. A missing
+    /// file has the default settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read, has an unknown key or the thread
+    /// count is not a number
+    pub fn load(path: &Path) -> Result<DuckDbSettings, Error> {
+        if !path.exists() {
+            return Ok(DuckDbSettings::default());
+        }
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read the settings file '{}'", path.display()))?;
+        DuckDbSettings::parse(&text)
+            .with_context(|| format!("Invalid settings file '{}'", path.display()))
+    }
+
+    /// Writes the settings file at `path`, creating its directory if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory or the file can't be written
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).with_context(|| {
+                format!("Failed to create the directory '{}'", directory.display())
+            })?;
+        }
+        fs::write(path, self.format())
+            .with_context(|| format!("Failed to write the settings file '{}'", path.display()))
+    }
+
+    // Parses `key = value` lines. Blank lines and lines starting with `#` are skipped
+    // and values may be wrapped in double quotes.
+    fn parse(text: &str) -> Result<DuckDbSettings, Error> {
+        let mut settings = DuckDbSettings::default();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| Error::msg(format!("Expected 'key = value' in '{}'", line)))?;
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+            match key.trim() {
+                "memory_limit" => settings.memory_limit = value.to_string(),
+                "threads" => settings.threads = parse_threads(value)?,
+                "temp_directory" => settings.temp_directory = value.to_string(),
+                key => return Err(Error::msg(format!("Unknown setting '{}'", key))),
+            }
+        }
+        Ok(settings)
+    }
+
+    fn format(&self) -> String {
+        format!(
+            "# DuckDB settings of the file viewer, empty values and 0 use DuckDB's defaults\n\
+             memory_limit = {}\n\
+             threads = {}\n\
+             temp_directory = {}\n",
+            self.memory_limit, self.threads, self.temp_directory
+        )
+    }
+
+    /// Sets the memory limit, thread count and temp directory on `conn`. Must be
+    /// called before the connection is locked down.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if DuckDB rejects a value, e.g. a memory limit of `lots`
+    pub fn apply(&self, conn: &Connection) -> Result<(), Error> {
+        if !self.memory_limit.is_empty() {
+            conn.execute_batch(&format!(
+                "SET memory_limit = {}",
+                string_literal(&self.memory_limit)
+            ))
+            .with_context(|| format!("Invalid memory limit '{}'", self.memory_limit))?;
+        }
+        if self.threads > 0 {
+            conn.execute_batch(&format!("SET threads = {}", self.threads))
+                .with_context(|| format!("Invalid thread count {}", self.threads))?;
+        }
+        if !self.temp_directory.is_empty() {
+            conn.execute_batch(&format!(
+                "SET temp_directory = {}",
+                string_literal(&self.temp_directory)
+            ))
+            .with_context(|| format!("Invalid temp directory '{}'", self.temp_directory))?;
+        }
+        Ok(())
+    }
+
+    /// The values DuckDB uses with these settings, with its defaults filled in, e.g.
+    /// a memory limit of `12.4 GiB` when none is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if DuckDB rejects a value
+    pub fn effective(&self) -> Result<DuckDbSettings, Error> {
+        let conn = Connection::open_in_memory()
+            .with_context(|| "Failed to set up duckdb connection".to_string())?;
+        self.apply(&conn)?;
+        conn.query_row(
+            "SELECT current_setting('memory_limit'), current_setting('threads'),
+                    current_setting('temp_directory')",
+            [],
+            |row| {
+                Ok(DuckDbSettings {
+                    memory_limit: row.get(0)?,
+                    threads: row.get::<_, i64>(1)?.try_into().unwrap_or(0),
+                    temp_directory: row.get(2)?,
+                })
+            },
+        )
+        .with_context(|| "Failed to read the DuckDB settings".to_string())
+    }
+}
+
+/// Parses a thread count, where an empty value is 0 for DuckDB's default.
+///
+/// # Errors
+///
+/// Returns an error if `value` is not a positive whole number
+pub fn parse_threads(value: &str) -> Result<u32, Error> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(0);
+    }
+    value
+        .parse()
+        .map_err(|_e| Error::msg(format!("The thread count '{}' is not a number", value)))
+}
+
+/// The settings file in the user's configuration directory, e.g.
+/// `%APPDATA%\slint_duckdb_viewer\settings.conf` or
+/// `~/.config/slint_duckdb_viewer/settings.conf`.
+pub fn default_config_path() -> Option<PathBuf> {
+    let directory = env::var_os("APPDATA")
+        .or_else(|| env::var_os("XDG_CONFIG_HOME"))
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(directory.join(CONFIG_DIRECTORY).join(CONFIG_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_file_round_trip() -> Result<(), Error> {
+        let settings = DuckDbSettings {
+            memory_limit: "4GB".to_string(),
+            threads: 3,
+            temp_directory: r"D:\spill space".to_string(),
+        };
+        let path = Path::new("target/settings_test/settings.conf");
+        settings.save(path)?;
+        assert_eq!(DuckDbSettings::load(path)?, settings);
+
+        // a missing file has the defaults
+        let missing = Path::new("target/settings_test/missing.conf");
+        assert_eq!(DuckDbSettings::load(missing)?, DuckDbSettings::default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_settings() -> Result<(), Error> {
+        let settings = DuckDbSettings::parse(
+            "# spill to the scratch disk\n\nmemory_limit = \"75%\"\nthreads=2\n",
+        )?;
+        assert_eq!(settings.memory_limit, "75%");
+        assert_eq!(settings.threads, 2);
+        assert_eq!(settings.temp_directory, "");
+
+        assert!(DuckDbSettings::parse("threads = many").is_err());
+        assert!(DuckDbSettings::parse("memory = 4GB").is_err());
+        assert!(DuckDbSettings::parse("memory_limit").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_settings() -> Result<(), Error> {
+        let settings = DuckDbSettings {
+            memory_limit: "512MB".to_string(),
+            threads: 2,
+            temp_directory: "target/settings_test/spill".to_string(),
+        };
+        let effective = settings.effective()?;
+        // DuckDB shows the limit in its own units
+        assert!(!effective.memory_limit.is_empty());
+        assert_ne!(
+            effective.memory_limit,
+            DuckDbSettings::default().effective()?.memory_limit
+        );
+        assert_eq!(effective.threads, 2);
+        assert_eq!(effective.temp_directory, "target/settings_test/spill");
+
+        let invalid = DuckDbSettings {
+            memory_limit: "lots".to_string(),
+            ..DuckDbSettings::default()
+        };
+        assert!(invalid.effective().is_err());
+        Ok(())
+    }
+}
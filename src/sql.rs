@@ -0,0 +1,79 @@
+/// Quotes a value such as a file path or sheet name as a SQL string literal.
+///
+/// Single quotes are doubled. Backslashes are kept as they are since DuckDB only
+/// treats them as escapes in `E'...'` strings, so Windows paths need no escaping.
+pub fn string_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Quotes an identifier such as a schema, table or column name for use in a query.
+///
+/// Double quotes are doubled, so any name, including keywords and names with spaces
+/// or dots, refers to exactly that column or table.
+pub fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// Calls the table function `function` on the file `path`, followed by `arguments`
+/// which are written as SQL, e.g. `read_json_auto('a.json', compression = 'gzip')`.
+pub fn scan(function: &str, path: &str, arguments: &str) -> String {
+    format!("{}({}{})", function, string_literal(path), arguments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string_literal() {
+        assert_eq!(string_literal("data.csv"), "'data.csv'");
+        assert_eq!(string_literal(""), "''");
+        // a quote ends the literal unless it is doubled
+        assert_eq!(
+            string_literal("O'Brien_export.csv"),
+            "'O''Brien_export.csv'"
+        );
+        assert_eq!(
+            string_literal("x'); COPY data TO 'out.csv'; --"),
+            "'x''); COPY data TO ''out.csv''; --'"
+        );
+    }
+
+    #[test]
+    fn test_string_literal_keeps_backslashes_and_unicode() {
+        assert_eq!(
+            string_literal(r"C:\Users\O'Brien\Exports\data.csv"),
+            r"'C:\Users\O''Brien\Exports\data.csv'"
+        );
+        assert_eq!(
+            string_literal(r"\\server\share\new\table.parquet"),
+            r"'\\server\share\new\table.parquet'"
+        );
+        assert_eq!(
+            string_literal("/tmp/Zürich 東京 📈.csv"),
+            "'/tmp/Zürich 東京 📈.csv'"
+        );
+    }
+
+    #[test]
+    fn test_quote_identifier() {
+        assert_eq!(quote_identifier("price"), "\"price\"");
+        assert_eq!(quote_identifier("select"), "\"select\"");
+        assert_eq!(quote_identifier("unit price (€)"), "\"unit price (€)\"");
+        assert_eq!(quote_identifier("a.b"), "\"a.b\"");
+        assert_eq!(quote_identifier("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(quote_identifier("it's"), "\"it's\"");
+    }
+
+    #[test]
+    fn test_scan() {
+        assert_eq!(
+            scan("read_json_auto", "it's.json", ", compression = 'gzip'"),
+            "read_json_auto('it''s.json', compression = 'gzip')"
+        );
+        assert_eq!(
+            scan("parquet_scan", r"D:\data\x.parquet", ""),
+            r"parquet_scan('D:\data\x.parquet')"
+        );
+    }
+}
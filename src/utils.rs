@@ -1,5 +1,10 @@
 
+use anyhow::{Context, Error};
+use calamine::{open_workbook, Reader, Xlsx};
 use chrono::{DateTime, Duration, NaiveDate, NaiveTime};
+use std::fs::File;
+use std::io::BufReader;
+use std::io::Read;
 use std::path::Path;
 
 pub fn file_exists(filename: &str) -> bool {
@@ -7,6 +12,50 @@ pub fn file_exists(filename: &str) -> bool {
     path.is_file()
 }
 
+/// Returns true if `filename` is a directory or a glob pattern, which are opened as
+/// a single parquet dataset. An existing file is never a dataset, even when its name
+/// has brackets such as `report [2024].csv`.
+pub fn is_dataset_path(filename: &str) -> bool {
+    let path = Path::new(filename);
+    !path.is_file() && (path.is_dir() || is_glob_pattern(filename))
+}
+
+/// Returns true if `filename` contains glob wildcards such as `*`, `?` or `[`.
+pub fn is_glob_pattern(filename: &str) -> bool {
+    filename.contains(['*', '?', '['])
+}
+
+/// Escapes the glob wildcards in the name of an existing file, which DuckDB's scans
+/// would otherwise expand. `sales[1].parquet` becomes `sales[[]1].parquet`.
+pub fn escape_glob(filename: &str) -> String {
+    filename
+        .chars()
+        .map(|c| match c {
+            '*' | '?' | '[' => format!("[{}]", c),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// Collects the hive partition keys of a dataset from its file paths, in the order
+/// they first appear. `dt=2025-01-01/part-0.parquet` has the key `dt`.
+pub fn hive_partition_keys(paths: &[String]) -> Vec<String> {
+    let mut keys: Vec<String> = Vec::new();
+    for path in paths {
+        let mut segments: Vec<&str> = path.split(['/', '\\']).collect();
+        // the last segment is the file name
+        segments.pop();
+        for segment in segments {
+            if let Some((key, _value)) = segment.split_once('=') {
+                if !key.is_empty() && !keys.iter().any(|k| k == key) {
+                    keys.push(key.to_string());
+                }
+            }
+        }
+    }
+    keys
+}
+
 
 /// Extracts the file extension from a given filename or path.
 ///
@@ -47,6 +96,61 @@ pub fn get_file_extension(filename: &str) -> String {
         .unwrap_or_default()
 }
 
+/// Splits a compression suffix off a filename and returns the extension of the
+/// compressed file together with DuckDB's name for the compression.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(get_file_format("data.csv.gz"), ("csv".to_string(), Some("gzip")));
+/// assert_eq!(get_file_format("data.tsv.zst"), ("tsv".to_string(), Some("zstd")));
+/// assert_eq!(get_file_format("data.parquet"), ("parquet".to_string(), None));
+/// ```
+pub fn get_file_format(filename: &str) -> (String, Option<&'static str>) {
+    let compression = match get_file_extension(filename).as_str() {
+        "gz" => Some("gzip"),
+        "zst" => Some("zstd"),
+        _ => None,
+    };
+    match compression {
+        Some(compression) => {
+            // drop the compression suffix and read the extension before it
+            let inner = &filename[..filename.rfind('.').unwrap_or(filename.len())];
+            (get_file_extension(inner), Some(compression))
+        }
+        None => (get_file_extension(filename), None),
+    }
+}
+
+/// Returns true if the file is read as csv, which is the only format with import options.
+pub fn is_csv_file(filename: &str) -> bool {
+    let (extension, _) = get_file_format(filename);
+    matches!(extension.as_str(), "csv" | "tsv") && !is_dataset_path(filename)
+}
+
+/// Returns true if the file or dataset is read as parquet, which has metadata to inspect.
+pub fn is_parquet_path(filename: &str) -> bool {
+    is_dataset_path(filename) || get_file_format(filename) == ("parquet".to_string(), None)
+}
+
+/// Returns true if the file starts with the SQLite database header.
+///
+/// Used to tell SQLite and DuckDB databases apart since both use the `.db` extension.
+pub fn is_sqlite_file(filename: &str) -> bool {
+    let mut header = [0u8; 16];
+    match File::open(filename).and_then(|mut file| file.read_exact(&mut header)) {
+        Ok(_) => &header == b"SQLite format 3\0",
+        Err(_) => false,
+    }
+}
+
+/// Lists the sheet names of an Excel workbook in workbook order.
+pub fn excel_sheet_names(filename: &str) -> Result<Vec<String>, Error> {
+    let workbook: Xlsx<BufReader<File>> = open_workbook(filename)
+        .with_context(|| format!("Failed to open workbook '{}'", filename))?;
+    Ok(workbook.sheet_names())
+}
+
 pub fn date32_to_ymd(date32: i32) -> String {
     let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
     let date = epoch + Duration::days(date32 as i64);
@@ -138,6 +242,89 @@ mod tests {
     use super::*;
     use duckdb::types::TimeUnit;
 
+    #[test]
+    fn test_get_file_format() {
+        assert_eq!(get_file_format("data.csv.gz"), ("csv".to_string(), Some("gzip")));
+        assert_eq!(
+            get_file_format("C:\\exports\\DATA.TSV.ZST"),
+            ("tsv".to_string(), Some("zstd"))
+        );
+        assert_eq!(
+            get_file_format("logs/app.jsonl.gz"),
+            ("jsonl".to_string(), Some("gzip"))
+        );
+        assert_eq!(get_file_format("data.csv"), ("csv".to_string(), None));
+        // a compressed file without an inner extension
+        assert_eq!(get_file_format("archive.gz"), ("".to_string(), Some("gzip")));
+    }
+
+    #[test]
+    fn test_is_csv_file() {
+        assert!(is_csv_file("test_data/AAPL_minute.csv"));
+        assert!(is_csv_file("exports/data.tsv.gz"));
+        assert!(!is_csv_file("test_data/AAPL_minute.parquet"));
+        assert!(!is_csv_file("exports/*.csv"));
+    }
+
+    #[test]
+    fn test_is_parquet_path() {
+        assert!(is_parquet_path("test_data/AAPL_minute.parquet"));
+        assert!(is_parquet_path("lake/dt=*/part-*.parquet"));
+        assert!(!is_parquet_path("test_data/AAPL_minute.csv"));
+        assert!(!is_parquet_path("test_data/AAPL_minute.parquet.gz"));
+    }
+
+    #[test]
+    fn test_is_dataset_path() {
+        assert!(is_dataset_path("test_data"));
+        assert!(is_dataset_path("lake/dt=*/part-*.parquet"));
+        assert!(is_dataset_path("lake/**/*.parquet"));
+        assert!(!is_dataset_path("test_data/AAPL_minute.parquet"));
+    }
+
+    #[test]
+    fn test_bracketed_file_is_not_a_dataset() {
+        std::fs::create_dir_all("target/glob_test").unwrap();
+        std::fs::copy("test_data/AAPL_minute.csv", "target/glob_test/utils [2024].csv").unwrap();
+        assert!(!is_dataset_path("target/glob_test/utils [2024].csv"));
+        assert!(is_csv_file("target/glob_test/utils [2024].csv"));
+        assert!(!is_parquet_path("target/glob_test/utils [2024].csv"));
+        // a pattern that is not an existing file is still a dataset
+        assert!(is_dataset_path("target/glob_test/sales[0-9].parquet"));
+
+        assert_eq!(escape_glob("sales[1].parquet"), "sales[[]1].parquet");
+        assert_eq!(escape_glob("data.csv"), "data.csv");
+    }
+
+    #[test]
+    fn test_hive_partition_keys() {
+        let paths = vec![
+            "lake/dt=2025-01-01/region=eu/part-0.parquet".to_string(),
+            "lake/dt=2025-01-02/region=us/part-0.parquet".to_string(),
+            "C:\\lake\\dt=2025-01-03\\region=eu\\part-1.parquet".to_string(),
+        ];
+        assert_eq!(hive_partition_keys(&paths), vec!["dt", "region"]);
+
+        // an '=' in the file name is not a partition
+        let paths = vec!["lake/part=0.parquet".to_string()];
+        assert!(hive_partition_keys(&paths).is_empty());
+    }
+
+    #[test]
+    fn test_is_sqlite_file() {
+        assert!(is_sqlite_file("test_data/products.sqlite"));
+        assert!(!is_sqlite_file("test_data/AAPL_minute.parquet"));
+        assert!(!is_sqlite_file("test_data/empty.parquet"));
+        assert!(!is_sqlite_file("test_data/does_not_exist.db"));
+    }
+
+    #[test]
+    fn test_excel_sheet_names() {
+        let sheets = excel_sheet_names("test_data/products.xlsx").unwrap();
+        assert_eq!(sheets, vec!["Products", "Summary"]);
+        assert!(excel_sheet_names("test_data/AAPL_minute.csv").is_err());
+    }
+
     #[test]
     fn test_date32_to_ymd() {
         assert_eq!(date32_to_ymd(19275), "2022-10-10");
@@ -0,0 +1,955 @@
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::{Context, Error};
+use tokio::sync::oneshot;
+
+use crate::csv_options::CsvOptions;
+use crate::error::LoaderError;
+use crate::filter::ColumnFilter;
+use crate::loader::fetch_data;
+use crate::model::{Filename, PageNumber, PageSize, QueryResult, SortSpec, TableView};
+use crate::parquet_metadata::ParquetMetadata;
+use crate::request::RequestTicket;
+use crate::session::{CsvDialect, FileSession, SharedCanceller};
+use crate::settings::DuckDbSettings;
+use crate::utils::is_csv_file;
+
+/// The tables of an opened database file or the sheets of an opened workbook,
+/// shown in the picker next to the table.
+pub enum SourceList {
+    Tables(Vec<String>),
+    Sheets(Vec<String>),
+}
+
+/// The outcome of a request sent to the loader thread.
+pub enum LoaderResponse {
+    /// The requested page of `filename`. `sources` lists the tables or sheets of the
+    /// file when it has just been opened
+    Page {
+        results: QueryResult,
+        filename: Filename,
+        sources: Option<SourceList>,
+    },
+    /// The query was cancelled. Holds the page that is still shown, if any
+    Cancelled(Option<TableView>),
+    /// The query failed and the previous rows are still shown. Holds the error and the
+    /// page they are shown with, if any
+    Failed {
+        error: Error,
+        shown: Option<TableView>,
+    },
+    /// A newer request was made, there is nothing to show
+    Superseded,
+    /// The csv file could not be read with the given import options. Holds the
+    /// reason so it can be shown next to the options
+    CsvOptionsRequired { filename: Filename, message: String },
+}
+
+enum LoaderCommand {
+    Open {
+        ticket: RequestTicket,
+        filename: Filename,
+        csv_options: CsvOptions,
+        view: TableView,
+        reply: oneshot::Sender<Result<LoaderResponse, Error>>,
+    },
+    Page {
+        ticket: RequestTicket,
+        view: TableView,
+        reply: oneshot::Sender<Result<LoaderResponse, Error>>,
+    },
+    SelectTable {
+        ticket: RequestTicket,
+        index: usize,
+        view: TableView,
+        reply: oneshot::Sender<Result<LoaderResponse, Error>>,
+    },
+    SelectSheet {
+        ticket: RequestTicket,
+        index: usize,
+        header_row: u32,
+        view: TableView,
+        reply: oneshot::Sender<Result<LoaderResponse, Error>>,
+    },
+    Filter {
+        ticket: RequestTicket,
+        filters: Vec<ColumnFilter>,
+        view: TableView,
+        reply: oneshot::Sender<Result<LoaderResponse, Error>>,
+    },
+    Search {
+        ticket: RequestTicket,
+        search: String,
+        view: TableView,
+        reply: oneshot::Sender<Result<LoaderResponse, Error>>,
+    },
+    RunQuery {
+        ticket: RequestTicket,
+        sql: Option<String>,
+        view: TableView,
+        reply: oneshot::Sender<Result<LoaderResponse, Error>>,
+    },
+    SniffCsv {
+        reply: oneshot::Sender<Result<CsvDialect, Error>>,
+    },
+    InspectParquet {
+        reply: oneshot::Sender<Result<ParquetMetadata, Error>>,
+    },
+}
+
+/// Handle to the loader thread that owns the DuckDB session of the opened file.
+///
+/// DuckDB work is blocking, so it runs on its own thread instead of the event loop
+/// or the tokio workers. Requests are sent as messages and the reply is awaited,
+/// which keeps the ui responsive while a file is scanned.
+#[derive(Clone)]
+pub struct Loader {
+    sender: mpsc::Sender<LoaderCommand>,
+    canceller: SharedCanceller,
+    settings: SharedSettings,
+}
+
+// The DuckDB settings applied to each file the loader thread opens
+type SharedSettings = Arc<Mutex<DuckDbSettings>>;
+
+impl Loader {
+    /// Starts the loader thread, which opens files with `settings`. It stops once every
+    /// `Loader` handle is dropped.
+    pub fn spawn(settings: DuckDbSettings) -> Result<Loader, Error> {
+        let (sender, receiver) = mpsc::channel();
+        let canceller: SharedCanceller = Arc::new(Mutex::new(None));
+        let settings: SharedSettings = Arc::new(Mutex::new(settings));
+
+        let thread_canceller = canceller.clone();
+        let thread_settings = settings.clone();
+        thread::Builder::new()
+            .name("duckdb-loader".to_string())
+            .spawn(move || run(receiver, thread_canceller, thread_settings))
+            .with_context(|| "Failed to start the loader thread".to_string())?;
+
+        Ok(Loader {
+            sender,
+            canceller,
+            settings,
+        })
+    }
+
+    /// Replaces the DuckDB settings. The opened file keeps the settings it was opened
+    /// with, they apply from the next file that is opened.
+    pub fn set_settings(&self, settings: DuckDbSettings) {
+        if let Ok(mut current) = self.settings.lock() {
+            *current = settings;
+        }
+    }
+
+    /// Opens `filename`, replacing the previously opened file, and loads its first page
+    /// of `page_size` rows. Csv files are read with `csv_options`.
+    pub async fn open(
+        &self,
+        ticket: RequestTicket,
+        filename: Filename,
+        csv_options: CsvOptions,
+        page_size: PageSize,
+    ) -> Result<LoaderResponse, Error> {
+        let (reply, response) = oneshot::channel();
+        self.send(LoaderCommand::Open {
+            ticket,
+            filename,
+            csv_options,
+            view: first_page(page_size),
+            reply,
+        })?;
+        response
+            .await
+            .map_err(|_e| Error::new(LoaderError::Stopped))?
+    }
+
+    /// Loads a page of the opened file.
+    pub async fn page(
+        &self,
+        ticket: RequestTicket,
+        page_number: PageNumber,
+        page_size: PageSize,
+        sort: SortSpec,
+    ) -> Result<LoaderResponse, Error> {
+        let view = TableView {
+            page_number: page_number.0,
+            page_size: page_size.0,
+            sort,
+        };
+        let (reply, response) = oneshot::channel();
+        self.send(LoaderCommand::Page {
+            ticket,
+            view,
+            reply,
+        })?;
+        response
+            .await
+            .map_err(|_e| Error::new(LoaderError::Stopped))?
+    }
+
+    /// Switches to another table of the opened database file and loads its first page
+    /// of `page_size` rows.
+    pub async fn select_table(
+        &self,
+        ticket: RequestTicket,
+        index: usize,
+        page_size: PageSize,
+    ) -> Result<LoaderResponse, Error> {
+        let (reply, response) = oneshot::channel();
+        self.send(LoaderCommand::SelectTable {
+            ticket,
+            index,
+            view: first_page(page_size),
+            reply,
+        })?;
+        response
+            .await
+            .map_err(|_e| Error::new(LoaderError::Stopped))?
+    }
+
+    /// Switches to another sheet of the opened workbook, reading the column names
+    /// from `header_row`, and loads its first page of `page_size` rows.
+    pub async fn select_sheet(
+        &self,
+        ticket: RequestTicket,
+        index: usize,
+        header_row: u32,
+        page_size: PageSize,
+    ) -> Result<LoaderResponse, Error> {
+        let (reply, response) = oneshot::channel();
+        self.send(LoaderCommand::SelectSheet {
+            ticket,
+            index,
+            header_row,
+            view: first_page(page_size),
+            reply,
+        })?;
+        response
+            .await
+            .map_err(|_e| Error::new(LoaderError::Stopped))?
+    }
+
+    /// Applies the filter bar predicates to the opened file and loads the first page of
+    /// the filtered rows, keeping the page size and sort.
+    pub async fn filter(
+        &self,
+        ticket: RequestTicket,
+        filters: Vec<ColumnFilter>,
+        page_size: PageSize,
+        sort: SortSpec,
+    ) -> Result<LoaderResponse, Error> {
+        let view = TableView {
+            page_number: 1,
+            page_size: page_size.0,
+            sort,
+        };
+        let (reply, response) = oneshot::channel();
+        self.send(LoaderCommand::Filter {
+            ticket,
+            filters,
+            view,
+            reply,
+        })?;
+        response
+            .await
+            .map_err(|_e| Error::new(LoaderError::Stopped))?
+    }
+
+    /// Narrows the opened file to the rows mentioning `search` in any column and loads
+    /// the first page of them, keeping the page size and sort. An empty `search` shows
+    /// every row again.
+    pub async fn search(
+        &self,
+        ticket: RequestTicket,
+        search: String,
+        page_size: PageSize,
+        sort: SortSpec,
+    ) -> Result<LoaderResponse, Error> {
+        let view = TableView {
+            page_number: 1,
+            page_size: page_size.0,
+            sort,
+        };
+        let (reply, response) = oneshot::channel();
+        self.send(LoaderCommand::Search {
+            ticket,
+            search,
+            view,
+            reply,
+        })?;
+        response
+            .await
+            .map_err(|_e| Error::new(LoaderError::Stopped))?
+    }
+
+    /// Runs `sql` over the `data` view of the opened file and loads the first page of
+    /// `page_size` rows of its result, or goes back to the opened file when `sql` is `None`.
+    pub async fn run_query(
+        &self,
+        ticket: RequestTicket,
+        sql: Option<String>,
+        page_size: PageSize,
+    ) -> Result<LoaderResponse, Error> {
+        let (reply, response) = oneshot::channel();
+        self.send(LoaderCommand::RunQuery {
+            ticket,
+            sql,
+            view: first_page(page_size),
+            reply,
+        })?;
+        response
+            .await
+            .map_err(|_e| Error::new(LoaderError::Stopped))?
+    }
+
+    /// Runs the csv sniffer on the opened csv file for the file info page.
+    pub async fn sniff_csv(&self) -> Result<CsvDialect, Error> {
+        let (reply, response) = oneshot::channel();
+        self.send(LoaderCommand::SniffCsv { reply })?;
+        response
+            .await
+            .map_err(|_e| Error::new(LoaderError::Stopped))?
+    }
+
+    /// Reads the metadata of the opened parquet file or dataset for the inspect page.
+    pub async fn inspect_parquet(&self) -> Result<ParquetMetadata, Error> {
+        let (reply, response) = oneshot::channel();
+        self.send(LoaderCommand::InspectParquet { reply })?;
+        response
+            .await
+            .map_err(|_e| Error::new(LoaderError::Stopped))?
+    }
+
+    /// Interrupts the query that is running on the opened file, if any.
+    pub fn cancel(&self) {
+        if let Ok(canceller) = self.canceller.lock() {
+            if let Some(canceller) = canceller.as_ref() {
+                canceller.cancel();
+            }
+        }
+    }
+
+    fn send(&self, command: LoaderCommand) -> Result<(), Error> {
+        self.sender
+            .send(command)
+            .map_err(|_e| Error::new(LoaderError::Stopped))
+    }
+}
+
+// The loader thread. Handles one request at a time until all senders are dropped.
+fn run(
+    receiver: mpsc::Receiver<LoaderCommand>,
+    canceller: SharedCanceller,
+    settings: SharedSettings,
+) {
+    let mut session: Option<FileSession> = None;
+
+    while let Ok(command) = receiver.recv() {
+        match command {
+            LoaderCommand::Open {
+                ticket,
+                filename,
+                csv_options,
+                view,
+                reply,
+            } => {
+                // close the previous file before opening the next one, and stop the
+                // cancel button interrupting its connection in case the open fails
+                session = None;
+                if let Ok(mut canceller) = canceller.lock() {
+                    *canceller = None;
+                }
+                let duckdb_settings = settings
+                    .lock()
+                    .map(|settings| settings.clone())
+                    .unwrap_or_default();
+                let response =
+                    match FileSession::open(filename.clone(), &csv_options, &duckdb_settings) {
+                        Ok(opened) => {
+                            let opened = session.insert(opened);
+                            // let the cancel button interrupt queries on the new connection
+                            if let Ok(mut canceller) = canceller.lock() {
+                                *canceller = Some(opened.canceller());
+                            }
+                            let sources = if opened.sheets().is_empty() {
+                                SourceList::Tables(
+                                    opened
+                                        .tables()
+                                        .iter()
+                                        .map(|table| table.display_name())
+                                        .collect(),
+                                )
+                            } else {
+                                SourceList::Sheets(opened.sheets().to_vec())
+                            };
+                            match load_page(opened, &ticket, view) {
+                                Ok(LoaderResponse::Page {
+                                    results, filename, ..
+                                }) => Ok(LoaderResponse::Page {
+                                    results,
+                                    filename,
+                                    sources: Some(sources),
+                                }),
+                                // the schema is read from a sample, a wrong guess of the
+                                // sniffer often shows once the rows are counted
+                                Err(e) if is_csv_file(&filename.0) && needs_csv_options(&e) => {
+                                    Ok(LoaderResponse::CsvOptionsRequired {
+                                        filename: filename.clone(),
+                                        message: format!("{:#}", e),
+                                    })
+                                }
+                                response => response,
+                            }
+                        }
+                        // the sniffer guessed wrong or the options don't fit the file,
+                        // let the user pick the import options
+                        Err(e) if is_csv_file(&filename.0) && needs_csv_options(&e) => {
+                            Ok(LoaderResponse::CsvOptionsRequired {
+                                filename: filename.clone(),
+                                message: format!("{:#}", e),
+                            })
+                        }
+                        Err(e) => Err(LoaderError::attach(e, &filename.0)),
+                    };
+                let _ = reply.send(response);
+            }
+            LoaderCommand::Page {
+                ticket,
+                view,
+                reply,
+            } => {
+                let response = match session.as_mut() {
+                    Some(session) => load_page(session, &ticket, view),
+                    // nothing to page through until a file has been opened
+                    None => Ok(LoaderResponse::Superseded),
+                };
+                let _ = reply.send(response);
+            }
+            LoaderCommand::SelectTable {
+                ticket,
+                index,
+                view,
+                reply,
+            } => {
+                let response = match session.as_mut() {
+                    Some(session) => match session.select_table(index) {
+                        Ok(_) => load_page(session, &ticket, view),
+                        Err(e) => Err(attach_error(session, e.context("Error reading the table"))),
+                    },
+                    None => Ok(LoaderResponse::Superseded),
+                };
+                let _ = reply.send(response);
+            }
+            LoaderCommand::SelectSheet {
+                ticket,
+                index,
+                header_row,
+                view,
+                reply,
+            } => {
+                let response = match session.as_mut() {
+                    Some(session) => match session.select_sheet(index, header_row) {
+                        Ok(_) => load_page(session, &ticket, view),
+                        Err(e) => Err(attach_error(session, e.context("Error reading the sheet"))),
+                    },
+                    None => Ok(LoaderResponse::Superseded),
+                };
+                let _ = reply.send(response);
+            }
+            LoaderCommand::Filter {
+                ticket,
+                filters,
+                view,
+                reply,
+            } => {
+                let response = match session.as_mut() {
+                    Some(session) => {
+                        let previous = session.set_filters(filters);
+                        match load_page(session, &ticket, view) {
+                            // a value that doesn't fit its column fails the query,
+                            // keep showing the rows of the previous filters
+                            Err(e) => {
+                                session.set_filters(previous);
+                                Ok(LoaderResponse::Failed {
+                                    error: e.context("Error applying the filters"),
+                                    shown: session.view(),
+                                })
+                            }
+                            Ok(LoaderResponse::Cancelled(shown)) => {
+                                session.set_filters(previous);
+                                Ok(LoaderResponse::Cancelled(shown))
+                            }
+                            response => response,
+                        }
+                    }
+                    None => Ok(LoaderResponse::Superseded),
+                };
+                let _ = reply.send(response);
+            }
+            LoaderCommand::Search {
+                ticket,
+                search,
+                view,
+                reply,
+            } => {
+                let response = match session.as_mut() {
+                    Some(session) => {
+                        let previous = session.set_search(search);
+                        match load_page(session, &ticket, view) {
+                            Err(e) => {
+                                session.set_search(previous);
+                                Ok(LoaderResponse::Failed {
+                                    error: e.context("Error searching the file"),
+                                    shown: session.view(),
+                                })
+                            }
+                            Ok(LoaderResponse::Cancelled(shown)) => {
+                                session.set_search(previous);
+                                Ok(LoaderResponse::Cancelled(shown))
+                            }
+                            response => response,
+                        }
+                    }
+                    None => Ok(LoaderResponse::Superseded),
+                };
+                let _ = reply.send(response);
+            }
+            LoaderCommand::RunQuery {
+                ticket,
+                sql,
+                view,
+                reply,
+            } => {
+                let response = match session.as_mut() {
+                    Some(session) => match session.set_query(sql.as_deref()) {
+                        // a query that fails while its rows are read or is cancelled
+                        // must not stay behind the table that is still shown
+                        Ok(previous) => match load_page(session, &ticket, view) {
+                            Err(e) => {
+                                session.restore_query(previous);
+                                Ok(LoaderResponse::Failed {
+                                    error: e,
+                                    shown: session.view(),
+                                })
+                            }
+                            Ok(LoaderResponse::Cancelled(_)) => {
+                                session.restore_query(previous);
+                                Ok(LoaderResponse::Cancelled(session.view()))
+                            }
+                            response => response,
+                        },
+                        // the columns of the query were being read when it was cancelled
+                        Err(_e) if session.take_cancelled() => {
+                            Ok(LoaderResponse::Cancelled(session.view()))
+                        }
+                        // the sql was typed by the user, show them what is wrong with it
+                        Err(e) => {
+                            let message = e.root_cause().to_string();
+                            Ok(LoaderResponse::Failed {
+                                error: e.context(LoaderError::Sql { message }),
+                                shown: session.view(),
+                            })
+                        }
+                    },
+                    None => Err(Error::msg("No file is open")),
+                };
+                let _ = reply.send(response);
+            }
+            LoaderCommand::SniffCsv { reply } => {
+                let response = match session.as_ref() {
+                    Some(session) => session
+                        .sniff_csv()
+                        .map_err(|e| attach_error(session, e.context("Error sniffing file"))),
+                    None => Err(Error::msg("No file is open")),
+                };
+                let _ = reply.send(response);
+            }
+            LoaderCommand::InspectParquet { reply } => {
+                let response = match session.as_ref() {
+                    Some(session) => session
+                        .inspect_parquet()
+                        .map_err(|e| attach_error(session, e.context("Error inspecting file"))),
+                    None => Err(Error::msg("No file is open")),
+                };
+                let _ = reply.send(response);
+            }
+        }
+    }
+}
+
+// The page shown when a file, table or query result is opened
+fn first_page(page_size: PageSize) -> TableView {
+    TableView {
+        page_number: 1,
+        page_size: page_size.0,
+        sort: SortSpec::default(),
+    }
+}
+
+// Loads the page described by `view` unless `ticket` is already out of date.
+fn load_page(
+    session: &mut FileSession,
+    ticket: &RequestTicket,
+    view: TableView,
+) -> Result<LoaderResponse, Error> {
+    // a newer request was made while this one was queued
+    if !ticket.is_current() {
+        return Ok(LoaderResponse::Superseded);
+    }
+
+    match fetch_data(
+        session,
+        PageNumber(view.page_number),
+        PageSize(view.page_size),
+        view.sort.clone(),
+    ) {
+        // the page is out of date, the newer request updates the ui
+        _ if !ticket.is_current() => {
+            session.take_cancelled();
+            Ok(LoaderResponse::Superseded)
+        }
+        Ok(results) => {
+            // a cancel that arrived after the query finished has nothing to cancel
+            session.take_cancelled();
+            session.set_view(view);
+            Ok(LoaderResponse::Page {
+                results,
+                filename: session.filename.clone(),
+                sources: None,
+            })
+        }
+        // the user cancelled the query. Keep the current rows and put the
+        // page and sort back to what they show
+        Err(_e) if session.take_cancelled() => Ok(LoaderResponse::Cancelled(session.view())),
+        Err(e) => Err(LoaderError::attach(e, &session.filename.0)),
+    }
+}
+
+// Attaches the kind of an error of a query on `session`. DuckDB fails every interrupted
+// query the same way, so only the cancel flag tells a query the user cancelled apart
+// from one interrupted for another reason
+fn attach_error(session: &FileSession, error: Error) -> Error {
+    if session.take_cancelled() {
+        error.context(LoaderError::Cancelled)
+    } else {
+        LoaderError::attach(error, &session.filename.0)
+    }
+}
+
+// True if the csv reader failed on the contents of the file, which the import options
+// may fix. Other errors such as running out of memory are shown as they are
+fn needs_csv_options(error: &Error) -> bool {
+    match LoaderError::classify(error, "") {
+        LoaderError::Parse { .. } => true,
+        // e.g. a column type override naming a column the sniffer didn't find
+        LoaderError::Sql { message } => message.to_lowercase().contains("csv"),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::FilterOperator;
+    use crate::model::{SortDirection, SortKey};
+    use crate::request::RequestTracker;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_loader_pages_through_file() -> Result<(), Error> {
+        let loader = Loader::spawn(DuckDbSettings::default())?;
+        let tracker = RequestTracker::new();
+
+        let opened = loader
+            .open(
+                tracker.begin(),
+                Filename("test_data/AAPL_minute.parquet".into()),
+                CsvOptions::default(),
+                PageSize(25),
+            )
+            .await?;
+        let first_page = match opened {
+            LoaderResponse::Page { results, .. } => results,
+            _ => panic!("Expected the first page of the opened file"),
+        };
+        assert_eq!(first_page.rows.len(), 25);
+
+        let second = loader
+            .page(
+                tracker.begin(),
+                PageNumber(2),
+                PageSize(50),
+                SortSpec::default(),
+            )
+            .await?;
+        match second {
+            LoaderResponse::Page { results, .. } => {
+                assert_eq!(results.rows.len(), 50);
+                assert_eq!(results.row_count, first_page.row_count);
+            }
+            _ => panic!("Expected page 2 of the opened file"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_loader_skips_superseded_requests() -> Result<(), Error> {
+        let loader = Loader::spawn(DuckDbSettings::default())?;
+        let tracker = RequestTracker::new();
+
+        let stale = tracker.begin();
+        let _latest = tracker.begin();
+        let response = loader
+            .open(
+                stale,
+                Filename("test_data/AAPL_minute.parquet".into()),
+                CsvOptions::default(),
+                PageSize(20),
+            )
+            .await?;
+        assert!(matches!(response, LoaderResponse::Superseded));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_page_drops_out_of_order_completions() -> Result<(), Error> {
+        let mut session = FileSession::open(
+            Filename("test_data/AAPL_minute.parquet".into()),
+            &CsvOptions::default(),
+            &DuckDbSettings::default(),
+        )?;
+        let tracker = RequestTracker::new();
+
+        // a slow sort request followed by a page request that finishes first
+        let sort_request = tracker.begin();
+        let page_request = tracker.begin();
+        let page_view = TableView {
+            page_number: 2,
+            page_size: 10,
+            sort: SortSpec::default(),
+        };
+        let page = load_page(&mut session, &page_request, page_view.clone())?;
+        assert!(matches!(page, LoaderResponse::Page { .. }));
+
+        // the sort request completes last and is dropped
+        let sorted_view = TableView {
+            page_number: 1,
+            page_size: 10,
+            sort: SortSpec(vec![SortKey {
+                column: 0,
+                direction: SortDirection::Descending,
+            }]),
+        };
+        let sorted = load_page(&mut session, &sort_request, sorted_view)?;
+        assert!(matches!(sorted, LoaderResponse::Superseded));
+        // the page of the newer request is still the one shown
+        assert_eq!(session.view(), Some(page_view));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_loader_keeps_the_shown_page_of_a_failed_filter() -> Result<(), Error> {
+        let loader = Loader::spawn(DuckDbSettings::default())?;
+        let tracker = RequestTracker::new();
+
+        let opened = loader
+            .open(
+                tracker.begin(),
+                Filename("test_data/AAPL_minute.parquet".into()),
+                CsvOptions::default(),
+                PageSize(20),
+            )
+            .await?;
+        let volume = match opened {
+            LoaderResponse::Page { results, .. } => results
+                .schema
+                .iter()
+                .position(|column| column.name == "Volume")
+                .expect("Expected a Volume column"),
+            _ => panic!("Expected the first page of the opened file"),
+        };
+        let sort = SortSpec(vec![SortKey {
+            column: volume,
+            direction: SortDirection::Descending,
+        }]);
+        loader
+            .page(tracker.begin(), PageNumber(3), PageSize(20), sort.clone())
+            .await?;
+
+        // a value that is not a number fails the query
+        let filters = vec![ColumnFilter {
+            column: volume,
+            operator: FilterOperator::Equals,
+            value: "abc".to_string(),
+            value2: String::new(),
+        }];
+        let response = loader
+            .filter(tracker.begin(), filters, PageSize(20), SortSpec::default())
+            .await?;
+        match response {
+            LoaderResponse::Failed { shown, .. } => assert_eq!(
+                shown,
+                Some(TableView {
+                    page_number: 3,
+                    page_size: 20,
+                    sort,
+                })
+            ),
+            _ => panic!("Expected the filter to fail"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_loader_keeps_the_shown_page_of_a_failed_query() -> Result<(), Error> {
+        let loader = Loader::spawn(DuckDbSettings::default())?;
+        let tracker = RequestTracker::new();
+
+        loader
+            .open(
+                tracker.begin(),
+                Filename("test_data/AAPL_minute.parquet".into()),
+                CsvOptions::default(),
+                PageSize(20),
+            )
+            .await?;
+        loader
+            .page(
+                tracker.begin(),
+                PageNumber(2),
+                PageSize(20),
+                SortSpec::default(),
+            )
+            .await?;
+
+        let response = loader
+            .run_query(
+                tracker.begin(),
+                Some("SELECT no_such_column FROM data".to_string()),
+                PageSize(20),
+            )
+            .await?;
+        match response {
+            LoaderResponse::Failed { shown, .. } => {
+                assert_eq!(shown.map(|view| view.page_number), Some(2))
+            }
+            _ => panic!("Expected the query to fail"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_loader_asks_for_csv_options() -> Result<(), Error> {
+        let loader = Loader::spawn(DuckDbSettings::default())?;
+        let tracker = RequestTracker::new();
+
+        // a type override that doesn't fit the column fails like a bad sniff
+        let csv_options = CsvOptions {
+            column_types: vec![("no_such_column".to_string(), "INTEGER".to_string())],
+            ..CsvOptions::default()
+        };
+        let response = loader
+            .open(
+                tracker.begin(),
+                Filename("test_data/AAPL_minute.csv".into()),
+                csv_options,
+                PageSize(20),
+            )
+            .await?;
+        match response {
+            LoaderResponse::CsvOptionsRequired { filename, message } => {
+                assert_eq!(filename.0, "test_data/AAPL_minute.csv");
+                assert!(!message.is_empty());
+            }
+            _ => panic!("Expected the csv import options to be requested"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_loader_asks_for_csv_options_after_the_sample() -> Result<(), Error> {
+        let loader = Loader::spawn(DuckDbSettings::default())?;
+        let tracker = RequestTracker::new();
+
+        // the schema is read from the first rows, the extra field only fails the count
+        let mut text = String::from("id,name\n");
+        for id in 0..30000 {
+            text.push_str(&format!("{},item {}\n", id, id));
+        }
+        text.push_str("30000,item 30000,unexpected\n");
+        fs::write("target/late_error.csv", text)?;
+
+        let response = loader
+            .open(
+                tracker.begin(),
+                Filename("target/late_error.csv".into()),
+                CsvOptions::default(),
+                PageSize(20),
+            )
+            .await?;
+        assert!(matches!(
+            response,
+            LoaderResponse::CsvOptionsRequired { .. }
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_needs_csv_options() {
+        let parse = Error::msg("Invalid Input Error: CSV Error on Line: 40000");
+        assert!(needs_csv_options(&parse));
+        let override_error =
+            Error::msg("Binder Error: COLUMN_TYPES error: Columns with names: \"x\" do not exist in the CSV File");
+        assert!(needs_csv_options(&override_error));
+
+        let not_csv = [
+            "Out of Memory Error: could not allocate block of size 256.0 KiB",
+            "INTERRUPT Error: Interrupted!",
+            "Invalid Input Error: Could not set option \"memory_limit\"",
+            "Binder Error: Referenced column \"x\" not found",
+        ];
+        for message in not_csv {
+            assert!(!needs_csv_options(&Error::msg(message)), "{}", message);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_loader_reports_missing_files() -> Result<(), Error> {
+        let loader = Loader::spawn(DuckDbSettings::default())?;
+        let tracker = RequestTracker::new();
+
+        // a missing csv file is not fixed by the import options
+        for filename in ["test_data/missing.csv", "test_data/missing.parquet"] {
+            let error = match loader
+                .open(
+                    tracker.begin(),
+                    Filename(filename.into()),
+                    CsvOptions::default(),
+                    PageSize(20),
+                )
+                .await
+            {
+                Err(e) => e,
+                Ok(_) => panic!("Expected '{}' to fail to open", filename),
+            };
+            assert_eq!(
+                LoaderError::of(&error),
+                LoaderError::FileNotFound {
+                    filename: filename.to_string()
+                }
+            );
+        }
+
+        Ok(())
+    }
+}